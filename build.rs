@@ -1,5 +1,256 @@
+// Turns src/mappings.txt into a perfect hash table of the mappings for the
+// target platform, see src/mapping/database.rs for the lookup side
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[path = "src/mapping/hash.rs"]
+mod hash;
+
+/// Vendor features and the USB vendor ids they keep
+const VENDORS: [(&str, u16); 6] = [
+    ("MAPPING_DB_MICROSOFT", 0x045e),
+    ("MAPPING_DB_SONY", 0x054c),
+    ("MAPPING_DB_NINTENDO", 0x057e),
+    ("MAPPING_DB_LOGITECH", 0x046d),
+    ("MAPPING_DB_VALVE", 0x28de),
+    ("MAPPING_DB_8BITDO", 0x2dc8),
+];
+
+/// Average number of keys per bucket
+const BUCKET_SIZE: usize = 4;
+
+/// Vendor id of a GUID made from the vendor and product ids, SDL stores it
+/// little endian in the third pair of bytes
+fn vendor_id(guid: &str) -> Option<u16> {
+    let bytes = guid.as_bytes();
+    if guid.len() != 32 || &bytes[12..16] != b"0000" || &bytes[20..24] != b"0000" {
+        return None;
+    }
+    let low = u8::from_str_radix(&guid[8..10], 16).ok()?;
+    let high = u8::from_str_radix(&guid[10..12], 16).ok()?;
+    Some(u16::from_le_bytes([low, high]))
+}
+
+fn platform_name() -> &'static str {
+    match env::var("CARGO_CFG_TARGET_OS").unwrap().as_str() {
+        "windows" => "Windows",
+        "macos" => "Mac OS X",
+        "android" => "Android",
+        "ios" => "iOS",
+        _ => "Linux",
+    }
+}
+
+/// The GUID the way `Guid` formats it
+fn normalize_guid(guid: &str) -> String {
+    if guid.eq_ignore_ascii_case("xinput") {
+        return "78696e70757400000000000000000000".to_owned();
+    }
+    guid.to_ascii_lowercase()
+}
+
+/// The bytes of a normalized GUID, the keys of the tables
+fn guid_bytes(guid: &str) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&guid[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    bytes
+}
+
+/// The GUID without its version, like `Guid::without_version`
+fn without_version(mut guid: [u8; 16]) -> [u8; 16] {
+    if guid[6..8] == [0, 0] && guid[10..12] == [0, 0] {
+        guid[12..14].copy_from_slice(&[0, 0]);
+    }
+    guid
+}
+
+/// The GUID of a line with the CRC of its `crc` field in it, the way SDL
+/// keys mappings
+fn line_guid(guid: &str, fields: &str) -> String {
+    let crc = fields
+        .split(',')
+        .find_map(|field| field.strip_prefix("crc:"))
+        .and_then(|crc| u16::from_str_radix(crc, 16).ok());
+    match crc {
+        Some(crc) if guid.len() == 32 => {
+            let [low, high] = crc.to_le_bytes();
+            format!("{}{:02x}{:02x}{}", &guid[..4], low, high, &guid[8..])
+        }
+        _ => guid.to_owned(),
+    }
+}
+
+/// Whether a line only applies under some hint or SDK version
+fn is_conditional(fields: &str) -> bool {
+    fields.split(',').any(|field| {
+        field.starts_with("hint:") || field.starts_with("sdk>=") || field.starts_with("sdk<=")
+    })
+}
+
+/// Entries of the database to keep, one per GUID. An entry is the last
+/// unconditional line for the GUID and the conditional lines after it, with
+/// their line numbers in the file. Lines start with their normalized GUID.
+fn entries(text: &str) -> Vec<Vec<(usize, String)>> {
+    let everything = env::var_os("CARGO_FEATURE_MAPPING_DB").is_some();
+    let vendors: Vec<u16> = VENDORS
+        .iter()
+        .filter(|(feature, _)| env::var_os(format!("CARGO_FEATURE_{}", feature)).is_some())
+        .map(|(_, vendor)| *vendor)
+        .collect();
+    let platform = format!("platform:{}", platform_name());
+
+    let mut entries: HashMap<String, Vec<(usize, String)>> = HashMap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.split(',').any(|field| field == platform) {
+            continue;
+        }
+
+        let (guid, rest) = line.split_once(',').unwrap();
+        let guid = line_guid(&normalize_guid(guid), rest);
+        let keep = everything || vendor_id(&guid).is_some_and(|vendor| vendors.contains(&vendor));
+        if keep {
+            let line = format!("{},{}", guid, rest);
+            let lines = entries.entry(guid).or_default();
+            if !is_conditional(rest) {
+                lines.clear();
+            }
+            lines.push((index + 1, line));
+        }
+    }
+
+    let mut entries: Vec<Vec<(usize, String)>> = entries.into_values().collect();
+    entries.sort_unstable();
+    entries
+}
+
+/// Hash and displace: keys are spread in buckets, then from the biggest
+/// bucket down, each bucket gets the first seed that puts all of its keys in
+/// free slots. Every slot ends up used.
+fn perfect_hash(guids: &[[u8; 16]]) -> (Vec<u32>, Vec<Option<usize>>) {
+    let bucket_count = guids.len().div_ceil(BUCKET_SIZE).max(1);
+    let mut buckets = vec![vec![]; bucket_count];
+    for (index, guid) in guids.iter().enumerate() {
+        buckets[(hash::hash(0, guid) % bucket_count as u64) as usize].push(index);
+    }
+
+    let mut order: Vec<usize> = (0..bucket_count).collect();
+    order.sort_by_key(|bucket| std::cmp::Reverse(buckets[*bucket].len()));
+
+    let mut displacements = vec![0; bucket_count];
+    let mut slots = vec![None; guids.len()];
+    for bucket in order {
+        if buckets[bucket].is_empty() {
+            continue;
+        }
+
+        'seeds: for seed in 1.. {
+            let mut taken = vec![];
+            for index in &buckets[bucket] {
+                let slot = (hash::hash(seed, &guids[*index]) % guids.len() as u64) as usize;
+                if slots[slot].is_some() || taken.contains(&slot) {
+                    continue 'seeds;
+                }
+                taken.push(slot);
+            }
+
+            for (index, slot) in buckets[bucket].iter().zip(taken) {
+                slots[slot] = Some(*index);
+            }
+            displacements[bucket] = seed;
+            break;
+        }
+    }
+
+    (displacements, slots)
+}
+
+/// A perfect hash table of `rows`, GUIDs and values of type `value_type`,
+/// as `{prefix}DISPLACEMENTS` and `{prefix}ENTRIES`. Returns the slot of each
+/// row.
+fn write_table(
+    out: &mut String,
+    prefix: &str,
+    value_type: &str,
+    rows: &[([u8; 16], String)],
+) -> Vec<usize> {
+    let guids: Vec<[u8; 16]> = rows.iter().map(|(guid, _)| *guid).collect();
+    let (displacements, slots) = perfect_hash(&guids);
+
+    writeln!(
+        out,
+        "const {}DISPLACEMENTS: [u32; {}] = [",
+        prefix,
+        displacements.len()
+    )
+    .unwrap();
+    for seed in displacements {
+        writeln!(out, "    {},", seed).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(
+        out,
+        "const {}ENTRIES: [([u8; 16], {}); {}] = [",
+        prefix,
+        value_type,
+        slots.len()
+    )
+    .unwrap();
+    let mut row_slots = vec![0; rows.len()];
+    for (slot, index) in slots.iter().enumerate() {
+        let (guid, value) = &rows[index.unwrap()];
+        writeln!(out, "    ({:?}, {}),", guid, value).unwrap();
+        row_slots[index.unwrap()] = slot;
+    }
+    writeln!(out, "];").unwrap();
+    row_slots
+}
+
 fn main() {
     if cfg!(target_os = "macos") {
         println!("cargo:rustc-link-lib=framework=IOKit");
     }
+
+    println!("cargo:rerun-if-changed=src/mappings.txt");
+    println!("cargo:rerun-if-changed=src/mapping/hash.rs");
+
+    let text = fs::read_to_string("src/mappings.txt").unwrap();
+    let rows: Vec<([u8; 16], String)> = entries(&text)
+        .into_iter()
+        .map(|lines| {
+            let guid = guid_bytes(lines[0].1.split(',').next().unwrap());
+            (guid, format!("&{:?}", lines))
+        })
+        .collect();
+
+    let mut out = String::new();
+    let slots = write_table(&mut out, "", "Lines", &rows);
+
+    // The slots of the entries of each GUID without its version, in the
+    // order of `entries`
+    let mut versions: Vec<([u8; 16], Vec<usize>)> = vec![];
+    for ((guid, _), slot) in rows.iter().zip(slots) {
+        let guid = without_version(*guid);
+        match versions.iter_mut().find(|(other, _)| *other == guid) {
+            Some((_, slots)) => slots.push(slot),
+            None => versions.push((guid, vec![slot])),
+        }
+    }
+    let rows: Vec<([u8; 16], String)> = versions
+        .into_iter()
+        .map(|(guid, slots)| (guid, format!("&{:?}", slots)))
+        .collect();
+    write_table(&mut out, "ANY_VERSION_", "&[usize]", &rows);
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("database.rs");
+    fs::write(path, out).unwrap();
 }
@@ -0,0 +1,51 @@
+use quad_gamepad::*;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Targets nothing is pressed for in that long are skipped
+const SKIP_AFTER: Duration = Duration::from_secs(5);
+
+pub fn main() {
+    let mut context = ControllerContext::new().unwrap();
+
+    context.update();
+
+    let id = context
+        .controllers()
+        .into_iter()
+        .find(|id| context.raw_input(*id).is_some())
+        .expect("No controller to map");
+    let mut session = MappingSession::new(&context.info(id));
+
+    println!("Mapping {}", context.info(id).name);
+
+    let mut asked = None;
+    let mut asked_at = Instant::now();
+    while let Some(target) = session.target() {
+        if asked != Some(session.step()) {
+            asked = Some(session.step());
+            asked_at = Instant::now();
+            println!(
+                "{}/{}: press {}",
+                session.step() + 1,
+                session.step_count(),
+                target.to_sdl_name().unwrap_or_default()
+            );
+        }
+
+        context.update();
+        if let Some(source) = session.update(&context.raw_input(id).unwrap()) {
+            println!("  {}", source.to_sdl_value());
+        } else if asked_at.elapsed() > SKIP_AFTER {
+            println!("  skipped");
+            session.skip();
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mapping = session.mapping().to_string();
+    println!("{}", mapping);
+    context.add_mapping(&mapping).unwrap();
+}
@@ -4,38 +4,31 @@ use std::thread;
 use std::time::Duration;
 
 pub fn main() {
-    let mut context = ControllerContext::new().unwrap();
-
-    context.update();
+    set_diagnostic_callback(|diagnostic| eprintln!("{}", diagnostic));
 
-    for i in 0..MAX_DEVICES {
-        let status = context.state(i).status;
-        if status == ControllerStatus::Connected {
-            println!("{:?}", context.info(i));
-        }
-    }
+    let mut context = ControllerContext::new().unwrap();
 
     loop {
         context.update();
 
-        for i in 0..MAX_DEVICES {
-            let state = context.state(i);
-            if state.status == ControllerStatus::Connected {
-                let info = context.info(i);
-
-                for button in info.buttons {
-                    if state.digital_state[button as usize]
-                        && !state.digital_state_prev[button as usize]
-                    {
-                        println!("{:?}", button);
-                    }
-
-                    for (axis, value) in state.analog_state.iter().enumerate() {
-                        if value.abs() >= 0.01 {
-                            println!("axis {} = {}", axis, value);
-                        }
-                    }
+        while let Some(event) = context.poll_event() {
+            match event {
+                GamepadEvent::Connected { id, .. } => {
+                    println!("connected {}: {:?}", id, context.info(id))
+                }
+                GamepadEvent::Disconnected { id, .. } => println!("disconnected {}", id),
+                GamepadEvent::ButtonPressed { id, button, .. } => {
+                    let info = context.info(id);
+                    println!(
+                        "{:?} ({})",
+                        button,
+                        info.controller_type.label(info.face_style, button)
+                    );
+                }
+                GamepadEvent::AxisChanged { axis, value, .. } if value.abs() >= 0.01 => {
+                    println!("{:?} = {}", axis, value);
                 }
+                _ => {}
             }
         }
 
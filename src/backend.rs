@@ -0,0 +1,62 @@
+use crate::mapping::Mappings;
+use crate::{ControllerInfo, ControllerState, Error, RawInput};
+
+/// Something for a controller to do, see `Backend::output`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Output {
+    /// Speed of the low frequency (left) and high frequency (right) rumble
+    /// motors, 0.0 to 1.0
+    Rumble { low: f32, high: f32 },
+}
+
+/// A source of controllers, like evdev or XInput. A `ControllerContext`
+/// reads from all of its backends, the ones of the platform and any added
+/// with `ControllerContext::add_backend`.
+///
+/// Devices are named by keys the backend picks, that it does not reuse
+/// while the device is open.
+pub trait Backend {
+    /// Open the devices that showed up since the last call and return their
+    /// keys. Devices whose vendor and product ids are `claimed` are driven by
+    /// another backend and must be left alone.
+    fn enumerate(
+        &mut self,
+        mappings: &Mappings,
+        claimed: &dyn Fn(u16, u16) -> bool,
+    ) -> Result<Vec<usize>, Error>;
+
+    /// Read the inputs of every open device, starting each state with
+    /// `ControllerState::begin_update`. Devices that are gone get the
+    /// `Disconnected` status.
+    fn poll(&mut self);
+
+    /// Forget a disconnected device, once the context reported it
+    fn close(&mut self, device: usize);
+
+    fn info(&self, device: usize) -> Option<&ControllerInfo>;
+
+    fn state(&self, device: usize) -> Option<&ControllerState>;
+
+    /// Whether the backend drives devices with these ids itself, other
+    /// backends then skip them
+    fn claims(&self, _vendor_id: u16, _product_id: u16) -> bool {
+        false
+    }
+
+    /// Inputs of a device before the mapping, for `MappingSession`. `None`
+    /// for devices with a fixed layout.
+    fn raw_input(&self, _device: usize) -> Option<RawInput> {
+        None
+    }
+
+    /// After mappings or hints changed, switch the devices whose mapping in
+    /// `mappings` is another one to it, see `Mapping::apply_raw`. The others
+    /// are left as they are.
+    fn remap(&mut self, _mappings: &Mappings) {}
+
+    /// False when the device or the backend cannot do it
+    fn output(&mut self, _device: usize, _output: Output) -> bool {
+        false
+    }
+}
@@ -0,0 +1,484 @@
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::diagnostic::{report, Diagnostic};
+use crate::event::EventQueue;
+use crate::mapping::{MappingLayer, Mappings, Platform};
+use crate::players::{ControllerId, Players};
+use crate::{
+    platform, Backend, ControllerInfo, ControllerState, ControllerStatus, Error, GamepadEvent,
+    Output, ParseError, RawInput, DEFAULT_CONTROLLER_STATE,
+};
+
+/// A connected controller
+struct Device {
+    id: ControllerId,
+    /// Index in `ControllerContext::backends`
+    backend: usize,
+    /// Key of the device in its backend
+    key: usize,
+}
+
+pub struct ControllerContext {
+    backends: Vec<Box<dyn Backend>>,
+    /// Whether the last enumeration of each backend failed, a failure is
+    /// reported when a backend starts failing and not on every update
+    failing: Vec<bool>,
+    devices: Vec<Device>,
+    mappings: Mappings,
+    players: Players,
+    events: EventQueue,
+}
+
+impl ControllerContext {
+    /// Open the controllers that are plugged in, with the backends of the
+    /// platform. Without /dev/input on Linux, in a container for example,
+    /// there are none and none will show up. A backend that fails to list
+    /// its controllers is reported as `Diagnostic::EnumerationFailed`, the
+    /// error is returned only when every backend fails.
+    pub fn new() -> Result<Self, Error> {
+        ControllerContext::with_backends(platform::backends()?)
+    }
+
+    /// A context that reads from `backends` only, in place of the ones of
+    /// the platform. Earlier backends get devices that several can drive.
+    pub fn with_backends(backends: Vec<Box<dyn Backend>>) -> Result<Self, Error> {
+        let mut context = ControllerContext {
+            failing: vec![false; backends.len()],
+            backends,
+            devices: vec![],
+            mappings: Mappings::new(Platform::current()),
+            players: Players::new(),
+            events: EventQueue::new(),
+        };
+        context.enumerate()?;
+        Ok(context)
+    }
+
+    /// Read from `backend` too, after the other backends. Like `new`, fails
+    /// only when every backend fails.
+    pub fn add_backend(&mut self, backend: Box<dyn Backend>) -> Result<(), Error> {
+        self.backends.push(backend);
+        self.failing.push(false);
+        self.enumerate()
+    }
+
+    /// Look for new controllers with every backend. The errors of backends
+    /// that were not failing yet are reported, except the first one when
+    /// every backend fails, which is returned.
+    fn enumerate(&mut self) -> Result<(), Error> {
+        let mut errors = vec![];
+        for index in 0..self.backends.len() {
+            let (before, rest) = self.backends.split_at_mut(index);
+            let (backend, after) = rest.split_first_mut().unwrap();
+            let claimed = |vendor_id, product_id| {
+                before
+                    .iter()
+                    .chain(after.iter())
+                    .any(|other| other.claims(vendor_id, product_id))
+            };
+
+            let keys = match backend.enumerate(&self.mappings, &claimed) {
+                Ok(keys) => keys,
+                Err(error) => {
+                    if !self.failing[index] {
+                        self.failing[index] = true;
+                        errors.push(error);
+                    }
+                    continue;
+                }
+            };
+            self.failing[index] = false;
+            for key in keys {
+                if let Some(info) = backend.info(key) {
+                    self.devices.push(Device {
+                        id: self.players.connect(info, Instant::now()),
+                        backend: index,
+                        key,
+                    });
+                }
+            }
+        }
+
+        let every_backend = self.failing.iter().all(|&failing| failing);
+        let mut errors = errors.into_iter();
+        let returned = if every_backend { errors.next() } else { None };
+        for error in errors {
+            report(Diagnostic::EnumerationFailed { error });
+        }
+        returned.map_or(Ok(()), Err)
+    }
+
+    fn device(&self, id: ControllerId) -> Option<(&dyn Backend, usize)> {
+        let device = self.devices.iter().find(|device| device.id == id)?;
+        Some((&*self.backends[device.backend], device.key))
+    }
+
+    /// Add a mapping in the SDL game controller format, over the mappings of
+    /// every other source. Connected controllers with its GUID switch to it
+    /// right away.
+    pub fn add_mapping(&mut self, mapping: &str) -> Result<(), ParseError> {
+        self.mappings.add_mapping(mapping)?;
+        self.remap();
+        Ok(())
+    }
+
+    /// Add the mappings for this platform of a database shipped with the
+    /// game, in the SDL game controller format like `gamecontrollerdb.txt`.
+    /// They win over the bundled database. Lines that fail to parse are
+    /// skipped and returned.
+    pub fn load_mappings_from_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> io::Result<Vec<ParseError>> {
+        let errors = self
+            .mappings
+            .add_mappings_from_file(MappingLayer::Game, path)?;
+        self.remap();
+        Ok(errors)
+    }
+
+    /// Add the mappings for this platform of the player's own mappings file.
+    /// They win over the game's and the bundled ones, and lose to the SDL
+    /// environment variables and `add_mapping`.
+    pub fn load_user_mappings_from_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> io::Result<Vec<ParseError>> {
+        let errors = self
+            .mappings
+            .add_mappings_from_file(MappingLayer::User, path)?;
+        self.remap();
+        Ok(errors)
+    }
+
+    /// Set a boolean SDL hint that mapping entries can depend on, like
+    /// `SDL_GAMECONTROLLER_USE_BUTTON_LABELS` for the Nintendo entries that
+    /// have labeled and positional variants. Hints that are not set come
+    /// from the environment variable of the same name, as it was when the
+    /// context was created.
+    pub fn set_hint(&mut self, name: &str, value: bool) {
+        self.mappings.set_hint(name, value);
+        self.remap();
+    }
+
+    fn remap(&mut self) {
+        for backend in &mut self.backends {
+            backend.remap(&self.mappings);
+        }
+    }
+
+    /// Inputs of a controller before the mapping, for `MappingSession`.
+    /// `None` for controllers with a fixed layout like the Steam Deck or
+    /// XInput ones.
+    pub fn raw_input(&self, id: ControllerId) -> Option<RawInput> {
+        let (backend, key) = self.device(id)?;
+        backend.raw_input(key)
+    }
+
+    /// Look for new controllers and read the inputs of every controller.
+    /// Controllers that are gone are dropped once their disconnection is
+    /// queued.
+    pub fn update(&mut self) {
+        // A failed enumeration is tried again on the next update, and only
+        // reported again once it has worked in between
+        if let Err(error) = self.enumerate() {
+            report(Diagnostic::EnumerationFailed { error });
+        }
+
+        for backend in &mut self.backends {
+            backend.poll();
+        }
+
+        let time = Instant::now();
+        for device in &self.devices {
+            let state = self.backends[device.backend]
+                .state(device.key)
+                .unwrap_or(&DEFAULT_CONTROLLER_STATE);
+            self.events.push_changes(device.id, state, time);
+        }
+
+        let (backends, players) = (&mut self.backends, &mut self.players);
+        self.devices.retain(|device| {
+            let backend = &mut backends[device.backend];
+            let connected = backend
+                .state(device.key)
+                .is_some_and(|state| state.status == ControllerStatus::Connected);
+            if !connected {
+                players.disconnect(device.id, time);
+                backend.close(device.key);
+            }
+            connected
+        });
+    }
+
+    /// The next change of a controller seen by `update`, oldest first
+    pub fn poll_event(&mut self) -> Option<GamepadEvent> {
+        self.events.pop()
+    }
+
+    /// The connected controllers, by player number
+    pub fn controllers(&self) -> Vec<ControllerId> {
+        self.players.ids()
+    }
+
+    /// The controller of player number `player`, counted from 0
+    pub fn controller(&self, player: usize) -> Option<ControllerId> {
+        self.players.id(player)
+    }
+
+    /// Player number of a connected controller. A controller that is
+    /// plugged back in gets its number back, unless another one took it.
+    pub fn player_index(&self, id: ControllerId) -> Option<usize> {
+        self.players.player(id)
+    }
+
+    /// Give player number `player` to controller `id`, the controller that
+    /// had it takes the old number of `id`. False when `id` is not connected.
+    pub fn set_player_index(&mut self, id: ControllerId, player: usize) -> bool {
+        self.players.set_player(id, player)
+    }
+
+    pub fn info(&self, id: ControllerId) -> ControllerInfo {
+        self.device(id)
+            .and_then(|(backend, key)| backend.info(key))
+            .cloned()
+            .unwrap_or_else(ControllerInfo::new)
+    }
+
+    pub fn state(&self, id: ControllerId) -> &ControllerState {
+        self.device(id)
+            .and_then(|(backend, key)| backend.state(key))
+            .unwrap_or(&DEFAULT_CONTROLLER_STATE)
+    }
+
+    /// Make a controller rumble for example. False when the controller is
+    /// not connected or cannot do it.
+    pub fn output(&mut self, id: ControllerId, output: Output) -> bool {
+        match self.devices.iter().find(|device| device.id == id) {
+            Some(device) => self.backends[device.backend].output(device.key, output),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GamepadButton;
+
+    /// Pads that are plugged in from the start and hold A down
+    struct Virtual {
+        plugged: Vec<ControllerInfo>,
+        pads: Vec<Option<(ControllerInfo, ControllerState)>>,
+        claims: bool,
+    }
+
+    impl Virtual {
+        fn boxed(ids: &[(u16, u16)], claims: bool) -> Box<dyn Backend> {
+            let plugged = ids
+                .iter()
+                .map(|&(vendor_id, product_id)| ControllerInfo {
+                    vendor_id,
+                    product_id,
+                    ..ControllerInfo::new()
+                })
+                .collect();
+            Box::new(Virtual {
+                plugged,
+                pads: vec![],
+                claims,
+            })
+        }
+    }
+
+    impl Backend for Virtual {
+        fn enumerate(
+            &mut self,
+            _mappings: &Mappings,
+            claimed: &dyn Fn(u16, u16) -> bool,
+        ) -> Result<Vec<usize>, Error> {
+            let mut keys = vec![];
+            for info in self.plugged.drain(..) {
+                if !claimed(info.vendor_id, info.product_id) {
+                    let mut state = ControllerState::new();
+                    state.status = ControllerStatus::Connected;
+                    keys.push(self.pads.len());
+                    self.pads.push(Some((info, state)));
+                }
+            }
+            Ok(keys)
+        }
+
+        fn poll(&mut self) {
+            for (_, state) in self.pads.iter_mut().flatten() {
+                state.begin_update();
+                state.set_button(GamepadButton::A, true);
+            }
+        }
+
+        fn close(&mut self, device: usize) {
+            self.pads[device] = None;
+        }
+
+        fn info(&self, device: usize) -> Option<&ControllerInfo> {
+            self.pads[device].as_ref().map(|(info, _)| info)
+        }
+
+        fn state(&self, device: usize) -> Option<&ControllerState> {
+            self.pads[device].as_ref().map(|(_, state)| state)
+        }
+
+        fn claims(&self, vendor_id: u16, product_id: u16) -> bool {
+            self.claims
+                && self
+                    .pads
+                    .iter()
+                    .flatten()
+                    .any(|(info, _)| info.vendor_id == vendor_id && info.product_id == product_id)
+        }
+
+        fn output(&mut self, device: usize, _output: Output) -> bool {
+            self.pads[device].is_some()
+        }
+    }
+
+    /// A backend that cannot list its devices
+    struct Broken;
+
+    impl Backend for Broken {
+        fn enumerate(
+            &mut self,
+            _mappings: &Mappings,
+            _claimed: &dyn Fn(u16, u16) -> bool,
+        ) -> Result<Vec<usize>, Error> {
+            Err(Error::Platform("broken".to_owned()))
+        }
+
+        fn poll(&mut self) {}
+
+        fn close(&mut self, _device: usize) {}
+
+        fn info(&self, _device: usize) -> Option<&ControllerInfo> {
+            None
+        }
+
+        fn state(&self, _device: usize) -> Option<&ControllerState> {
+            None
+        }
+    }
+
+    /// A backend whose enumerations fail or not in the order of `fails`
+    struct Flaky {
+        fails: std::vec::IntoIter<bool>,
+    }
+
+    impl Backend for Flaky {
+        fn enumerate(
+            &mut self,
+            _mappings: &Mappings,
+            _claimed: &dyn Fn(u16, u16) -> bool,
+        ) -> Result<Vec<usize>, Error> {
+            match self.fails.next() {
+                Some(true) => Err(Error::Platform("flaky".to_owned())),
+                _ => Ok(vec![]),
+            }
+        }
+
+        fn poll(&mut self) {}
+
+        fn close(&mut self, _device: usize) {}
+
+        fn info(&self, _device: usize) -> Option<&ControllerInfo> {
+            None
+        }
+
+        fn state(&self, _device: usize) -> Option<&ControllerState> {
+            None
+        }
+    }
+
+    #[test]
+    fn failing_backend() {
+        let mut context = ControllerContext::with_backends(vec![
+            Box::new(Broken),
+            Virtual::boxed(&[(1, 2)], false),
+        ])
+        .unwrap();
+        assert_eq!(context.controllers().len(), 1);
+
+        // Backends after it still find their controllers
+        context
+            .add_backend(Virtual::boxed(&[(3, 4)], false))
+            .unwrap();
+        assert_eq!(context.controllers().len(), 2);
+
+        assert!(
+            ControllerContext::with_backends(vec![Box::new(Broken), Box::new(Broken)]).is_err()
+        );
+        assert!(ControllerContext::with_backends(vec![]).is_ok());
+    }
+
+    #[test]
+    fn failure_reported_once() {
+        use std::sync::{Arc, Mutex};
+
+        let _lock = crate::diagnostic::TEST_LOCK
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let reported = Arc::new(Mutex::new(0));
+        let sink = reported.clone();
+        crate::set_diagnostic_callback(move |diagnostic| {
+            if let Diagnostic::EnumerationFailed {
+                error: Error::Platform(message),
+            } = diagnostic
+            {
+                if message == "flaky" {
+                    *sink.lock().unwrap() += 1;
+                }
+            }
+        });
+
+        let flaky = Box::new(Flaky {
+            fails: vec![true, true, false, true].into_iter(),
+        });
+        let mut context =
+            ControllerContext::with_backends(vec![flaky, Virtual::boxed(&[], false)]).unwrap();
+        context.update();
+        assert_eq!(*reported.lock().unwrap(), 1);
+
+        // Reported again after it worked once
+        context.update();
+        context.update();
+        crate::set_diagnostic_callback(|_| {});
+        assert_eq!(*reported.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn several_backends() {
+        let mut context = ControllerContext::with_backends(vec![
+            Virtual::boxed(&[(1, 2)], true),
+            Virtual::boxed(&[(1, 2), (3, 4)], false),
+        ])
+        .unwrap();
+
+        // The first backend drives 1:2, the second one leaves it alone
+        let ids = context.controllers();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(context.info(ids[0]).vendor_id, 1);
+        assert_eq!(context.info(ids[1]).vendor_id, 3);
+
+        context.update();
+        let events: Vec<GamepadEvent> = std::iter::from_fn(|| context.poll_event()).collect();
+        assert_eq!(events.len(), 4);
+        assert!(context.state(ids[1]).is_down(GamepadButton::A));
+        assert!(context.output(
+            ids[1],
+            Output::Rumble {
+                low: 1.0,
+                high: 0.0
+            }
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::GamepadButton;
+
+const VENDOR_MICROSOFT: u16 = 0x045e;
+const VENDOR_SONY: u16 = 0x054c;
+const VENDOR_NINTENDO: u16 = 0x057e;
+const VENDOR_VALVE: u16 = 0x28de;
+const VENDOR_8BITDO: u16 = 0x2dc8;
+
+/// Known controllers by vendor and product id, from SDL's controller_list.h
+const CONTROLLERS: [(u16, u16, ControllerType); 40] = [
+    (VENDOR_MICROSOFT, 0x028e, ControllerType::Xbox360),
+    (VENDOR_MICROSOFT, 0x028f, ControllerType::Xbox360),
+    (VENDOR_MICROSOFT, 0x0291, ControllerType::Xbox360),
+    (VENDOR_MICROSOFT, 0x02a1, ControllerType::Xbox360),
+    (VENDOR_MICROSOFT, 0x0719, ControllerType::Xbox360),
+    (VENDOR_MICROSOFT, 0x02d1, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x02dd, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x02e0, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x02e3, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x02ea, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x02fd, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x0b00, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x0b05, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x0b0a, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x0b20, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x0b22, ControllerType::XboxOne),
+    (VENDOR_MICROSOFT, 0x0b12, ControllerType::XboxSeries),
+    (VENDOR_MICROSOFT, 0x0b13, ControllerType::XboxSeries),
+    (VENDOR_SONY, 0x0268, ControllerType::PS3),
+    (VENDOR_SONY, 0x05c4, ControllerType::PS4),
+    (VENDOR_SONY, 0x09cc, ControllerType::PS4),
+    (VENDOR_SONY, 0x0ba0, ControllerType::PS4),
+    (VENDOR_SONY, 0x0ce6, ControllerType::PS5),
+    (VENDOR_SONY, 0x0df2, ControllerType::PS5),
+    (VENDOR_NINTENDO, 0x2009, ControllerType::SwitchPro),
+    (VENDOR_NINTENDO, 0x2006, ControllerType::JoyConLeft),
+    (VENDOR_NINTENDO, 0x2007, ControllerType::JoyConRight),
+    (VENDOR_NINTENDO, 0x200e, ControllerType::JoyConPair),
+    (VENDOR_VALVE, 0x1102, ControllerType::Steam),
+    (VENDOR_VALVE, 0x1106, ControllerType::Steam),
+    (VENDOR_VALVE, 0x1142, ControllerType::Steam),
+    (VENDOR_VALVE, 0x1205, ControllerType::SteamDeck),
+    // 8BitDo pads for Xbox, and the others in D-input mode. In X-input mode
+    // they report the ids of a Microsoft controller.
+    (VENDOR_8BITDO, 0x2000, ControllerType::XboxOne),
+    (VENDOR_8BITDO, 0x3106, ControllerType::XboxOne),
+    (VENDOR_8BITDO, 0x6000, ControllerType::EightBitDo),
+    (VENDOR_8BITDO, 0x6001, ControllerType::EightBitDo),
+    (VENDOR_8BITDO, 0x6003, ControllerType::EightBitDo),
+    (VENDOR_8BITDO, 0x6006, ControllerType::EightBitDo),
+    (VENDOR_8BITDO, 0x6100, ControllerType::EightBitDo),
+    (VENDOR_8BITDO, 0x6101, ControllerType::EightBitDo),
+];
+
+/// What is printed on the face buttons, the `face` field of SDL mappings.
+/// Named after the labels of the bottom, right, left and top buttons, so
+/// `Bayx` is the Nintendo layout when `GamepadButton::A` is the bottom one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FaceStyle {
+    #[default]
+    Abxy,
+    Axby,
+    Bayx,
+    /// Cross, Circle, Square and Triangle
+    Sony,
+}
+
+impl FaceStyle {
+    pub fn from_sdl_name(name: &str) -> Option<FaceStyle> {
+        match name {
+            "abxy" => Some(FaceStyle::Abxy),
+            "axby" => Some(FaceStyle::Axby),
+            "bayx" => Some(FaceStyle::Bayx),
+            "sony" => Some(FaceStyle::Sony),
+            _ => None,
+        }
+    }
+
+    pub fn to_sdl_name(self) -> &'static str {
+        match self {
+            FaceStyle::Abxy => "abxy",
+            FaceStyle::Axby => "axby",
+            FaceStyle::Bayx => "bayx",
+            FaceStyle::Sony => "sony",
+        }
+    }
+
+    /// Labels of `GamepadButton::A`, `B`, `X` and `Y`
+    fn labels(self) -> [&'static str; 4] {
+        match self {
+            FaceStyle::Abxy => ["A", "B", "X", "Y"],
+            FaceStyle::Axby => ["A", "X", "B", "Y"],
+            FaceStyle::Bayx => ["B", "A", "Y", "X"],
+            FaceStyle::Sony => ["Cross", "Circle", "Square", "Triangle"],
+        }
+    }
+}
+
+/// Family of a controller, for showing the right button prompts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ControllerType {
+    /// Anything else, shown with Xbox labels like SDL does
+    #[default]
+    Generic,
+    Xbox360,
+    XboxOne,
+    XboxSeries,
+    PS3,
+    PS4,
+    PS5,
+    SwitchPro,
+    JoyConLeft,
+    JoyConRight,
+    /// Both Joy-Cons in the charging grip, or combined as one controller
+    JoyConPair,
+    /// The Steam Controller
+    Steam,
+    SteamDeck,
+    /// 8BitDo controllers in D-input mode, with the Nintendo face button
+    /// layout
+    EightBitDo,
+}
+
+impl ControllerType {
+    pub fn from_ids(vendor_id: u16, product_id: u16) -> ControllerType {
+        CONTROLLERS
+            .iter()
+            .find(|(vendor, product, _)| *vendor == vendor_id && *product == product_id)
+            .map_or(ControllerType::Generic, |(_, _, kind)| *kind)
+    }
+
+    pub fn is_xbox(self) -> bool {
+        use ControllerType::*;
+        matches!(self, Xbox360 | XboxOne | XboxSeries)
+    }
+
+    pub fn is_playstation(self) -> bool {
+        use ControllerType::*;
+        matches!(self, PS3 | PS4 | PS5)
+    }
+
+    /// Controllers with B at the bottom and A on the right of the face
+    /// buttons, where a positional `GamepadButton::A` is labeled B
+    pub fn has_nintendo_layout(self) -> bool {
+        use ControllerType::*;
+        matches!(
+            self,
+            SwitchPro | JoyConLeft | JoyConRight | JoyConPair | EightBitDo
+        )
+    }
+
+    /// The face button labels of the family, for mappings that name the
+    /// buttons by position and have no `face` field
+    pub fn face_style(self) -> FaceStyle {
+        if self.is_playstation() {
+            FaceStyle::Sony
+        } else if self.has_nintendo_layout() {
+            FaceStyle::Bayx
+        } else {
+            FaceStyle::Abxy
+        }
+    }
+
+    /// What is printed on `button`, with the face buttons labeled by `face`,
+    /// the `ControllerInfo::face_style` of the controller. With positional
+    /// mappings `A` on Nintendo controllers, the bottom face button, is
+    /// labeled "B".
+    pub fn label(self, face: FaceStyle, button: GamepadButton) -> &'static str {
+        use ControllerType::*;
+        use GamepadButton::*;
+
+        if self.is_playstation() {
+            match button {
+                BumperLeft => return "L1",
+                BumperRight => return "R1",
+                TriggerLeft => return "L2",
+                TriggerRight => return "R2",
+                ThumbLeft => return "L3",
+                ThumbRight => return "R3",
+                Guide => return "PS",
+                _ => {}
+            }
+        }
+        if self.has_nintendo_layout() {
+            match button {
+                BumperLeft => return "L",
+                BumperRight => return "R",
+                TriggerLeft => return "ZL",
+                TriggerRight => return "ZR",
+                Back => return "-",
+                Start => return "+",
+                Guide => return "Home",
+                Misc1 => return "Capture",
+                _ => {}
+            }
+        }
+
+        match (self, button) {
+            (PS3, Back) => "Select",
+            (PS4, Back) => "Share",
+            (PS5, Back) => "Create",
+            (PS4 | PS5, Start) => "Options",
+            (PS5, Misc1) => "Mute",
+            (XboxOne | XboxSeries, Back) => "View",
+            (XboxOne | XboxSeries, Start) => "Menu",
+            (XboxSeries, Misc1) => "Share",
+            (SteamDeck, TriggerLeft) => "L2",
+            (SteamDeck, TriggerRight) => "R2",
+            (SteamDeck, Back) => "View",
+            (SteamDeck, Start) => "Menu",
+            (SteamDeck, Guide) => "Steam",
+            (SteamDeck, Misc1) => "...",
+            (SteamDeck, Paddle1) => "R4",
+            (SteamDeck, Paddle2) => "L4",
+            (SteamDeck, Paddle3) => "R5",
+            (SteamDeck, Paddle4) => "L5",
+            (Steam, Guide) => "Steam",
+            (Steam, Paddle1) => "RG",
+            (Steam, Paddle2) => "LG",
+            (_, A) => face.labels()[0],
+            (_, B) => face.labels()[1],
+            (_, X) => face.labels()[2],
+            (_, Y) => face.labels()[3],
+            (_, DpadUp) => "Up",
+            (_, DpadDown) => "Down",
+            (_, DpadLeft) => "Left",
+            (_, DpadRight) => "Right",
+            (_, BumperLeft) => "LB",
+            (_, BumperRight) => "RB",
+            (_, TriggerLeft) => "LT",
+            (_, TriggerRight) => "RT",
+            (_, ThumbLeft) => "LS",
+            (_, ThumbRight) => "RS",
+            (_, Back) => "Back",
+            (_, Start) => "Start",
+            (_, Guide) => "Guide",
+            (_, Misc1) => "Misc",
+            (_, Misc2) => "Misc2",
+            (_, Misc3) => "Misc3",
+            (_, Misc4) => "Misc4",
+            (_, Misc5) => "Misc5",
+            (_, Misc6) => "Misc6",
+            (_, Touchpad) => "Touchpad",
+            (_, Paddle1) => "P1",
+            (_, Paddle2) => "P2",
+            (_, Paddle3) => "P3",
+            (_, Paddle4) => "P4",
+        }
+    }
+
+    /// Identifier of the prompt image for `button`, the family of the
+    /// controller and its label like `ps4_cross`, `switch_b` or `xbox_view`
+    pub fn glyph(self, face: FaceStyle, button: GamepadButton) -> String {
+        use ControllerType::*;
+
+        let family = match self {
+            Generic => "generic",
+            Xbox360 => "xbox360",
+            XboxOne | XboxSeries => "xbox",
+            PS3 => "ps3",
+            PS4 => "ps4",
+            PS5 => "ps5",
+            SwitchPro | JoyConLeft | JoyConRight | JoyConPair => "switch",
+            Steam => "steam",
+            SteamDeck => "steamdeck",
+            EightBitDo => "8bitdo",
+        };
+        let label = match self.label(face, button) {
+            "-" => "minus",
+            "+" => "plus",
+            "..." => "quick_access",
+            label => label,
+        };
+        format!("{}_{}", family, label.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GamepadButton::*;
+
+    #[test]
+    fn from_ids() {
+        assert_eq!(
+            ControllerType::from_ids(VENDOR_8BITDO, 0x6001),
+            ControllerType::EightBitDo
+        );
+        assert_eq!(
+            ControllerType::from_ids(VENDOR_8BITDO, 0x3106),
+            ControllerType::XboxOne
+        );
+        assert_eq!(
+            ControllerType::from_ids(VENDOR_8BITDO, 0x1234),
+            ControllerType::Generic
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let xbox = ControllerType::XboxSeries;
+        let face = xbox.face_style();
+        assert_eq!(xbox.label(face, A), "A");
+        assert_eq!(xbox.label(face, Back), "View");
+        assert_eq!(xbox.label(face, TriggerLeft), "LT");
+        assert_eq!(xbox.glyph(face, Misc1), "xbox_share");
+
+        let ps4 = ControllerType::PS4;
+        let face = ps4.face_style();
+        assert_eq!(ps4.label(face, A), "Cross");
+        assert_eq!(ps4.label(face, Y), "Triangle");
+        assert_eq!(ps4.label(face, Back), "Share");
+        assert_eq!(ps4.glyph(face, B), "ps4_circle");
+
+        // Named by position, labeled the Nintendo way
+        let switch = ControllerType::SwitchPro;
+        let face = switch.face_style();
+        assert_eq!(switch.label(face, A), "B");
+        assert_eq!(switch.label(face, X), "Y");
+        assert_eq!(switch.label(face, TriggerRight), "ZR");
+        assert_eq!(switch.glyph(face, Start), "switch_plus");
+        let eight_bit_do = ControllerType::EightBitDo;
+        assert_eq!(eight_bit_do.glyph(eight_bit_do.face_style(), A), "8bitdo_b");
+
+        // Named by label
+        assert_eq!(switch.label(FaceStyle::Abxy, A), "A");
+        assert_eq!(switch.glyph(FaceStyle::Abxy, X), "switch_x");
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::{ControllerInfo, Error, Guid, MappingOrigin};
+
+type Callback = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+static CALLBACK: Mutex<Option<Callback>> = Mutex::new(None);
+
+/// Held by the tests that set the callback, which every test reports to
+#[cfg(test)]
+pub(crate) static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// What the backends noticed about the devices, for the logs of the game.
+/// They go to the callback of `set_diagnostic_callback`, and to the `log`
+/// crate with the `log` feature.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// A controller was opened. `path` is the device node on Linux, the
+    /// location on macOS and the user index on Windows.
+    DeviceFound {
+        name: String,
+        guid: Guid,
+        path: Option<String>,
+    },
+    /// A device that could have been a controller and was left alone
+    DeviceRejected { path: String, reason: &'static str },
+    /// The mapping a controller got, on connection or after mappings changed
+    MappingChosen {
+        name: String,
+        guid: Guid,
+        origin: MappingOrigin,
+    },
+    /// No mapping for the controller, it gets the default layout
+    MappingMissing { name: String, guid: Guid },
+    /// Reading from a controller failed for another reason than it being
+    /// unplugged. The controller is dropped.
+    ReadError { path: String, error: io::Error },
+    /// A backend failed to look for controllers. The other backends go on
+    /// without it, and it tries again on the next update.
+    EnumerationFailed { error: Error },
+}
+
+impl Diagnostic {
+    /// The mapping `info` got, chosen or missing
+    pub(crate) fn mapping(info: &ControllerInfo) -> Diagnostic {
+        if info.mapping.is_fallback() {
+            Diagnostic::MappingMissing {
+                name: info.name.clone(),
+                guid: info.guid,
+            }
+        } else {
+            Diagnostic::MappingChosen {
+                name: info.name.clone(),
+                guid: info.guid,
+                origin: info.mapping.clone(),
+            }
+        }
+    }
+
+    #[cfg(feature = "log")]
+    fn level(&self) -> log::Level {
+        match self {
+            Diagnostic::DeviceFound { .. } | Diagnostic::MappingChosen { .. } => log::Level::Info,
+            Diagnostic::DeviceRejected { .. } => log::Level::Debug,
+            Diagnostic::MappingMissing { .. } => log::Level::Warn,
+            Diagnostic::ReadError { .. } | Diagnostic::EnumerationFailed { .. } => {
+                log::Level::Error
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::DeviceFound { name, guid, path } => {
+                write!(f, "found {:?} ({})", name, guid)?;
+                if let Some(path) = path {
+                    write!(f, " at {}", path)?;
+                }
+                Ok(())
+            }
+            Diagnostic::DeviceRejected { path, reason } => {
+                write!(f, "skipped {}: {}", path, reason)
+            }
+            Diagnostic::MappingChosen { name, guid, origin } => {
+                write!(f, "{:?} ({}) mapped from {}", name, guid, origin)
+            }
+            Diagnostic::MappingMissing { name, guid } => {
+                write!(
+                    f,
+                    "no mapping for {:?} ({}), using the default one",
+                    name, guid
+                )
+            }
+            Diagnostic::ReadError { path, error } => {
+                write!(f, "reading {} failed: {}", path, error)
+            }
+            Diagnostic::EnumerationFailed { error } => {
+                write!(f, "looking for controllers failed: {}", error)
+            }
+        }
+    }
+}
+
+/// Receive every `Diagnostic`, from any context and thread, in place of the
+/// previous callback. The callback may set another one.
+pub fn set_diagnostic_callback<F: Fn(&Diagnostic) + Send + Sync + 'static>(callback: F) {
+    *CALLBACK.lock().unwrap_or_else(|err| err.into_inner()) = Some(Arc::new(callback));
+}
+
+pub(crate) fn report(diagnostic: Diagnostic) {
+    #[cfg(feature = "log")]
+    log::log!(diagnostic.level(), "{}", diagnostic);
+
+    // Not called under the lock, for callbacks that report or set a callback
+    let callback = CALLBACK
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    if let Some(callback) = callback {
+        callback(&diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        set_diagnostic_callback(move |diagnostic| {
+            // Other tests report too
+            if let Diagnostic::DeviceRejected {
+                reason: "no axes", ..
+            } = diagnostic
+            {
+                sink.lock().unwrap().push(diagnostic.to_string());
+                set_diagnostic_callback(|_| {});
+            }
+        });
+
+        report(Diagnostic::DeviceRejected {
+            path: "/dev/input/event99".to_owned(),
+            reason: "no axes",
+        });
+        assert_eq!(
+            *received.lock().unwrap(),
+            ["skipped /dev/input/event99: no axes"]
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+use std::io;
+
+/// Why `ControllerContext::new` failed. A machine without any input devices
+/// is not an error, the context then has no controllers.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Listing or opening input devices failed
+    Io(io::Error),
+    /// The platform refused to give access to its controllers, like the
+    /// macOS HID manager failing to open
+    Platform(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "input devices: {}", err),
+            Error::Platform(reason) => write!(f, "controller API unavailable: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Platform(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
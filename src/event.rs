@@ -0,0 +1,207 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use crate::{ControllerId, ControllerState, ControllerStatus, GamepadAxis, GamepadButton};
+
+/// Events a game has not polled yet are dropped past that many, oldest first
+const MAX_EVENTS: usize = 1024;
+
+/// A change of a controller, from one `ControllerContext::update` to the
+/// next. `id` is the controller and `time` when the update saw the change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    Connected {
+        id: ControllerId,
+        time: Instant,
+    },
+    Disconnected {
+        id: ControllerId,
+        time: Instant,
+    },
+    ButtonPressed {
+        id: ControllerId,
+        button: GamepadButton,
+        time: Instant,
+    },
+    ButtonReleased {
+        id: ControllerId,
+        button: GamepadButton,
+        time: Instant,
+    },
+    /// Axes without a name are not reported, they are only in the
+    /// `RawInput` of the controller
+    AxisChanged {
+        id: ControllerId,
+        axis: GamepadAxis,
+        value: f32,
+        time: Instant,
+    },
+}
+
+impl GamepadEvent {
+    pub fn id(&self) -> ControllerId {
+        match *self {
+            GamepadEvent::Connected { id, .. }
+            | GamepadEvent::Disconnected { id, .. }
+            | GamepadEvent::ButtonPressed { id, .. }
+            | GamepadEvent::ButtonReleased { id, .. }
+            | GamepadEvent::AxisChanged { id, .. } => id,
+        }
+    }
+
+    pub fn time(&self) -> Instant {
+        match *self {
+            GamepadEvent::Connected { time, .. }
+            | GamepadEvent::Disconnected { time, .. }
+            | GamepadEvent::ButtonPressed { time, .. }
+            | GamepadEvent::ButtonReleased { time, .. }
+            | GamepadEvent::AxisChanged { time, .. } => time,
+        }
+    }
+}
+
+/// What the queue last reported of a controller
+#[derive(Clone, Copy)]
+struct Reported {
+    buttons: [bool; GamepadButton::ALL.len()],
+    analog: [f32; GamepadAxis::ALL.len()],
+}
+
+const NOTHING_REPORTED: Reported = Reported {
+    buttons: [false; GamepadButton::ALL.len()],
+    analog: [0.0; GamepadAxis::ALL.len()],
+};
+
+/// Events of every backend, made by comparing the state of each controller
+/// after an update with what was last reported
+pub(crate) struct EventQueue {
+    events: VecDeque<GamepadEvent>,
+    /// Connected controllers, by id
+    reported: HashMap<ControllerId, Reported>,
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        EventQueue {
+            events: VecDeque::new(),
+            reported: HashMap::new(),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<GamepadEvent> {
+        self.events.pop_front()
+    }
+
+    /// Queue what changed in the state of controller `id`. Controllers
+    /// connect before their first presses and disconnect without releasing
+    /// what they held. Backends report the disconnected state of a
+    /// controller once before forgetting it.
+    pub fn push_changes(&mut self, id: ControllerId, state: &ControllerState, time: Instant) {
+        let events = &mut self.events;
+
+        if state.status != ControllerStatus::Connected {
+            if self.reported.remove(&id).is_some() {
+                events.push_back(GamepadEvent::Disconnected { id, time });
+            }
+            return;
+        }
+
+        let reported = self.reported.entry(id).or_insert_with(|| {
+            events.push_back(GamepadEvent::Connected { id, time });
+            NOTHING_REPORTED
+        });
+
+        for button in GamepadButton::ALL {
+            let index = button as usize;
+            let mut down = reported.buttons[index];
+            let mut presses = state.press_count[index];
+            let mut releases = state.release_count[index];
+
+            // Every edge of the update, taps faster than the updates
+            // included, then whatever the counts do not explain
+            loop {
+                if down && releases > 0 {
+                    releases -= 1;
+                } else if !down && presses > 0 {
+                    presses -= 1;
+                } else if down != state.digital_state[index] {
+                    presses = 0;
+                    releases = 0;
+                } else {
+                    break;
+                }
+                down = !down;
+                events.push_back(if down {
+                    GamepadEvent::ButtonPressed { id, button, time }
+                } else {
+                    GamepadEvent::ButtonReleased { id, button, time }
+                });
+            }
+            reported.buttons[index] = down;
+        }
+
+        for axis in GamepadAxis::ALL {
+            let value = state.axis(axis);
+            if value != reported.analog[axis as usize] {
+                reported.analog[axis as usize] = value;
+                events.push_back(GamepadEvent::AxisChanged {
+                    id,
+                    axis,
+                    value,
+                    time,
+                });
+            }
+        }
+
+        while self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::players::Players;
+    use crate::ControllerInfo;
+
+    #[test]
+    fn taps_between_updates() {
+        let mut queue = EventQueue::new();
+        let mut state = ControllerState::new();
+        let time = Instant::now();
+        let id = Players::new().connect(&ControllerInfo::new(), time);
+        state.status = ControllerStatus::Connected;
+        state.set_button(GamepadButton::A, true);
+        state.set_button(GamepadButton::A, false);
+        state.set_button(GamepadButton::A, true);
+        queue.push_changes(id, &state, time);
+
+        let events: Vec<GamepadEvent> = std::iter::from_fn(|| queue.pop()).collect();
+        let button = GamepadButton::A;
+        assert_eq!(
+            events[..4],
+            [
+                GamepadEvent::Connected { id, time },
+                GamepadEvent::ButtonPressed { id, button, time },
+                GamepadEvent::ButtonReleased { id, button, time },
+                GamepadEvent::ButtonPressed { id, button, time },
+            ]
+        );
+
+        state.begin_update();
+        state.set_button(GamepadButton::A, false);
+        state.set_button(GamepadButton::A, true);
+        queue.push_changes(id, &state, time);
+        assert!(state.was_released(button) && state.was_pressed(button) && state.is_down(button));
+        assert_eq!(
+            queue.pop(),
+            Some(GamepadEvent::ButtonReleased { id, button, time })
+        );
+        assert_eq!(
+            queue.pop(),
+            Some(GamepadEvent::ButtonPressed { id, button, time })
+        );
+        assert_eq!(queue.pop(), None);
+    }
+}
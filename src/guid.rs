@@ -0,0 +1,258 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// SDL joystick GUID, the key of mappings.
+///
+/// Made of little endian 16 bit words: bus, CRC16 of the name, vendor, 0,
+/// product, 0, version, and a driver signature byte and its data byte. Devices
+/// without vendor and product ids have the start of their name in place of
+/// the ids.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Guid([u8; 16]);
+
+/// `xinput` in mappings, the letters take the place of the CRC
+const XINPUT: Guid = Guid(*b"xinput\0\0\0\0\0\0\0\0\0\0");
+
+impl Guid {
+    pub const fn from_bytes(bytes: [u8; 16]) -> Guid {
+        Guid(bytes)
+    }
+
+    /// GUID of a device with vendor and product ids, without name CRC
+    pub fn from_ids(bus: u16, vendor: u16, product: u16, version: u16) -> Guid {
+        let mut guid = Guid::default();
+        guid.set_word(0, bus);
+        guid.set_word(2, vendor);
+        guid.set_word(4, product);
+        guid.set_word(6, version);
+        guid
+    }
+
+    /// GUID of a device without vendor and product ids, from the first 11
+    /// bytes of its name
+    pub fn from_name(bus: u16, name: &[u8]) -> Guid {
+        let mut guid = Guid::default();
+        guid.set_word(0, bus);
+        let len = name.len().min(11);
+        guid.0[4..4 + len].copy_from_slice(&name[..len]);
+        guid
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    fn word(&self, index: usize) -> u16 {
+        u16::from_le_bytes([self.0[index * 2], self.0[index * 2 + 1]])
+    }
+
+    fn set_word(&mut self, index: usize, value: u16) {
+        self.0[index * 2..index * 2 + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Whether the GUID holds vendor, product and version ids rather than a
+    /// name
+    pub fn has_ids(&self) -> bool {
+        self.word(3) == 0 && self.word(5) == 0
+    }
+
+    /// Linux `BUS_*` value, also used by SDL on the other platforms
+    pub fn bus(&self) -> u16 {
+        self.word(0)
+    }
+
+    /// CRC16 of the device name, 0 in GUIDs that do not have one
+    pub fn crc(&self) -> u16 {
+        if *self == XINPUT {
+            return 0;
+        }
+        self.word(1)
+    }
+
+    pub fn vendor(&self) -> Option<u16> {
+        Some(self.word(2)).filter(|_| self.has_ids())
+    }
+
+    pub fn product(&self) -> Option<u16> {
+        Some(self.word(4)).filter(|_| self.has_ids())
+    }
+
+    pub fn version(&self) -> Option<u16> {
+        Some(self.word(6)).filter(|_| self.has_ids())
+    }
+
+    pub fn with_crc(mut self, crc: u16) -> Guid {
+        if self == XINPUT {
+            return self;
+        }
+        self.set_word(1, crc);
+        self
+    }
+
+    /// The GUID with the CRC of `name` in it, like SDL 2.26 and later make
+    /// them
+    pub fn with_name_crc(self, name: &[u8]) -> Guid {
+        self.with_crc(crc16(name))
+    }
+
+    pub fn without_crc(self) -> Guid {
+        self.with_crc(0)
+    }
+
+    pub fn without_version(mut self) -> Guid {
+        if self.has_ids() {
+            self.set_word(6, 0);
+        }
+        self
+    }
+
+    /// Whether a mapping made for `self` applies to `device`, the SDL way: a
+    /// mapping without CRC matches any CRC, and with `match_version` off the
+    /// versions may differ
+    pub fn matches(&self, device: &Guid, match_version: bool) -> bool {
+        let mut mapping = *self;
+        let mut device = *device;
+        if mapping.crc() == 0 {
+            device = device.without_crc();
+        }
+        if !match_version {
+            mapping = mapping.without_version();
+            device = device.without_version();
+        }
+        mapping == device
+    }
+}
+
+/// CRC-16/ARC, what `SDL_crc16` computes
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseGuidError;
+
+impl fmt::Display for ParseGuidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GUID is not 32 hexadecimal digits")
+    }
+}
+
+impl std::error::Error for ParseGuidError {}
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    /// 32 hexadecimal digits, or `xinput` like SDL accepts
+    fn from_str(s: &str) -> Result<Guid, ParseGuidError> {
+        if s.eq_ignore_ascii_case("xinput") {
+            return Ok(XINPUT);
+        }
+
+        if s.len() != 32 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(ParseGuidError);
+        }
+        let mut guid = Guid::default();
+        for (i, byte) in guid.0.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| ParseGuidError)?;
+        }
+        Ok(guid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XBOX_360: &str = "030000005e0400008e02000014010000";
+
+    #[test]
+    fn parse_and_display() {
+        let guid: Guid = XBOX_360.parse().unwrap();
+        assert_eq!(guid.to_string(), XBOX_360);
+        assert_eq!(XBOX_360.to_uppercase().parse(), Ok(guid));
+        assert_eq!(
+            "030000005e0400008e0200001401000".parse::<Guid>(),
+            Err(ParseGuidError)
+        );
+        assert_eq!(
+            "030000005e0400008e0200001401000g".parse::<Guid>(),
+            Err(ParseGuidError)
+        );
+
+        let xinput: Guid = "XInput".parse().unwrap();
+        assert_eq!(xinput.to_string(), "78696e70757400000000000000000000");
+        assert_eq!(xinput.crc(), 0);
+        assert_eq!(xinput.without_crc(), xinput);
+    }
+
+    #[test]
+    fn ids() {
+        let guid: Guid = XBOX_360.parse().unwrap();
+        assert_eq!(guid, Guid::from_ids(3, 0x045e, 0x028e, 0x0114));
+        assert_eq!(guid.bus(), 3);
+        assert_eq!(guid.vendor(), Some(0x045e));
+        assert_eq!(guid.product(), Some(0x028e));
+        assert_eq!(guid.version(), Some(0x0114));
+
+        let named = Guid::from_name(5, b"Some Bluetooth Pad");
+        assert!(!named.has_ids());
+        assert_eq!(named.vendor(), None);
+        assert_eq!(named.product(), None);
+        assert_eq!(named.version(), None);
+    }
+
+    #[test]
+    fn name_crc() {
+        // The check value of CRC-16/ARC
+        assert_eq!(crc16(b"123456789"), 0xbb3d);
+        let guid: Guid = XBOX_360.parse().unwrap();
+        assert_eq!(
+            guid.with_name_crc(b"123456789").to_string(),
+            "03003dbb5e0400008e02000014010000"
+        );
+    }
+
+    #[test]
+    fn matching() {
+        let mapping: Guid = XBOX_360.parse().unwrap();
+        let device = mapping.with_crc(0x1234);
+
+        // A mapping without CRC is for every name
+        assert!(mapping.matches(&device, true));
+        assert!(device.matches(&device, true));
+        assert!(!device.matches(&mapping, true));
+        assert!(!device.matches(&mapping.with_crc(0x4321), true));
+
+        let newer = Guid::from_ids(3, 0x045e, 0x028e, 0x0115);
+        assert!(!mapping.matches(&newer, true));
+        assert!(mapping.matches(&newer, false));
+        assert!(mapping.matches(&newer.with_crc(0x1234), false));
+        assert!(!mapping.matches(&Guid::from_ids(3, 0x045e, 0x028f, 0x0114), false));
+    }
+}
@@ -9,20 +9,69 @@ extern crate core_foundation;
 #[cfg(target_os = "macos")]
 extern crate libc;
 
-mod mapping;
+mod backend;
+mod context;
+mod controller_type;
+mod diagnostic;
+mod error;
+mod event;
+mod guid;
+pub mod mapping;
 mod platform;
+mod players;
 
+pub use self::backend::{Backend, Output};
+pub use self::context::ControllerContext;
+pub use self::controller_type::{ControllerType, FaceStyle};
+pub use self::diagnostic::{set_diagnostic_callback, Diagnostic};
+pub use self::error::Error;
+pub use self::event::GamepadEvent;
+pub use self::guid::{Guid, ParseGuidError};
+pub use self::mapping::wizard::{MappingSession, RawInput};
+pub use self::mapping::{MappingLayer, MappingOrigin, ParseError, ParseErrorKind};
 pub use self::platform::*;
+pub use self::players::ControllerId;
 
-pub const MAX_DEVICES: usize = 8;
-pub const MAX_DIGITAL: usize = 16;
-pub const MAX_ANALOG: usize = 8;
+pub const MAX_TOUCHPADS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusType {
+    Usb,
+    Bluetooth,
+    /// uinput and other software devices
+    Virtual,
+    /// Any other bus, with the raw bus id reported by the platform
+    Other(u16),
+}
 
 #[derive(Debug, Clone)]
 pub struct ControllerInfo {
     pub name: String,
     pub buttons: Vec<GamepadButton>,
+    /// Values of `ControllerState::analog_state` the controller fills
     pub analog_count: usize,
+    pub touchpad_count: usize,
+    pub has_motion: bool,
+    /// 0 when the platform does not report it
+    pub vendor_id: u16,
+    /// 0 when the platform does not report it
+    pub product_id: u16,
+    /// 0 when the platform does not report it
+    pub version: u16,
+    pub bus: BusType,
+    /// SDL compatible GUID, the key used to look up mappings
+    pub guid: Guid,
+    /// Family of the controller, for its button labels
+    pub controller_type: ControllerType,
+    /// What is printed on the face buttons as the mapping names them, for
+    /// `ControllerType::label`
+    pub face_style: FaceStyle,
+    /// Serial number, EVIOCGUNIQ on Linux
+    pub serial: Option<String>,
+    /// Where the controller is plugged in, EVIOCGPHYS on Linux
+    pub physical_path: Option<String>,
+    /// Which entry the mapping of the controller comes from
+    pub mapping: MappingOrigin,
 }
 
 impl ControllerInfo {
@@ -31,12 +80,34 @@ impl ControllerInfo {
             name: "null".to_owned(),
             analog_count: 0,
             buttons: vec![],
+            touchpad_count: 0,
+            has_motion: false,
+            vendor_id: 0,
+            product_id: 0,
+            version: 0,
+            bus: BusType::Other(0),
+            guid: Guid::default(),
+            controller_type: ControllerType::Generic,
+            face_style: FaceStyle::Abxy,
+            serial: None,
+            physical_path: None,
+            mapping: MappingOrigin::default(),
         }
     }
 }
 
+impl Default for ControllerInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Buttons by position, named like the Xbox ones: `A` is the bottom face
+/// button whatever is printed on it, see `ControllerType::label`. Mappings
+/// that follow the labels instead say so in `ControllerInfo::face_style`.
 #[repr(usize)]
-#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GamepadButton {
     /// Also Cross
     A = 0,
@@ -52,13 +123,104 @@ pub enum GamepadButton {
     DpadLeft,
     BumperLeft,
     BumperRight,
+    /// The left trigger pushed past half way, or a digital trigger
+    TriggerLeft,
+    /// The right trigger pushed past half way, or a digital trigger
+    TriggerRight,
     ThumbLeft,
     ThumbRight,
-    Select,
-    Start,
+    /// Also Select, Share, View, Create or minus
     Back,
-    Unknown,
-    Max,
+    /// Also Options, Menu or plus
+    Start,
+    /// Xbox, PS or Home button
+    Guide,
+    /// Share on Xbox Series, Capture on Switch, Mute on DualSense
+    Misc1,
+    /// Extra buttons some controllers have, `misc2`..`misc6` in SDL 3
+    /// mappings
+    Misc2,
+    Misc3,
+    Misc4,
+    Misc5,
+    Misc6,
+    /// Touchpad click
+    Touchpad,
+    /// Upper right back button, R4 on the Steam Deck
+    Paddle1,
+    /// Upper left back button, L4 on the Steam Deck
+    Paddle2,
+    /// Lower right back button, R5 on the Steam Deck
+    Paddle3,
+    /// Lower left back button, L5 on the Steam Deck
+    Paddle4,
+}
+
+impl GamepadButton {
+    /// Every button, in `digital_state` order
+    pub const ALL: [GamepadButton; BUTTON_COUNT] = [
+        GamepadButton::A,
+        GamepadButton::B,
+        GamepadButton::X,
+        GamepadButton::Y,
+        GamepadButton::DpadUp,
+        GamepadButton::DpadDown,
+        GamepadButton::DpadRight,
+        GamepadButton::DpadLeft,
+        GamepadButton::BumperLeft,
+        GamepadButton::BumperRight,
+        GamepadButton::TriggerLeft,
+        GamepadButton::TriggerRight,
+        GamepadButton::ThumbLeft,
+        GamepadButton::ThumbRight,
+        GamepadButton::Back,
+        GamepadButton::Start,
+        GamepadButton::Guide,
+        GamepadButton::Misc1,
+        GamepadButton::Misc2,
+        GamepadButton::Misc3,
+        GamepadButton::Misc4,
+        GamepadButton::Misc5,
+        GamepadButton::Misc6,
+        GamepadButton::Touchpad,
+        GamepadButton::Paddle1,
+        GamepadButton::Paddle2,
+        GamepadButton::Paddle3,
+        GamepadButton::Paddle4,
+    ];
+}
+
+/// Size of the per button arrays of `ControllerState`
+const BUTTON_COUNT: usize = 28;
+
+/// The named axes, where `leftx`, `lefty`.. of mappings land in
+/// `ControllerState::analog_state`. Sticks go from -1.0 to 1.0, right and
+/// down positive like SDL on every platform, triggers from 0.0 released to
+/// 1.0.
+#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftX = 0,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub(crate) const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftX,
+        GamepadAxis::LeftY,
+        GamepadAxis::RightX,
+        GamepadAxis::RightY,
+        GamepadAxis::LeftTrigger,
+        GamepadAxis::RightTrigger,
+    ];
+
+    pub fn is_trigger(self) -> bool {
+        self == GamepadAxis::LeftTrigger || self == GamepadAxis::RightTrigger
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -67,13 +229,61 @@ pub enum ControllerStatus {
     Connected,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchpadState {
+    pub touched: bool,
+    /// The pad is clicked down
+    pub pressed: bool,
+    /// -1.0 (left) to 1.0 (right)
+    pub x: f32,
+    /// -1.0 (top) to 1.0 (bottom)
+    pub y: f32,
+    /// 0.0 to 1.0
+    pub pressure: f32,
+}
+
+const DEFAULT_TOUCHPAD_STATE: TouchpadState = TouchpadState {
+    touched: false,
+    pressed: false,
+    x: 0.0,
+    y: 0.0,
+    pressure: 0.0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionState {
+    /// Acceleration in g
+    pub accel: [f32; 3],
+    /// Angular velocity in degrees per second
+    pub gyro: [f32; 3],
+}
+
+const DEFAULT_MOTION_STATE: MotionState = MotionState {
+    accel: [0.0; 3],
+    gyro: [0.0; 3],
+};
+
+/// Snapshot of a controller in the standard layout, cheap to copy. Inputs
+/// of the device beyond that layout, like the extra axes of a flight stick,
+/// are in its `RawInput`.
+#[derive(Debug, Clone, Copy)]
 pub struct ControllerState {
     pub status: ControllerStatus,
     pub sequence: usize,
-    pub digital_state_prev: [bool; GamepadButton::Max as usize],
-    pub digital_state: [bool; GamepadButton::Max as usize],
-    pub analog_state: [f32; MAX_ANALOG],
+    pub digital_state_prev: [bool; BUTTON_COUNT],
+    pub digital_state: [bool; BUTTON_COUNT],
+    /// How many times each button went down during the last update, more
+    /// than once for taps faster than the updates. XInput and the macOS HID
+    /// backend only read levels, so there they count at most one edge.
+    pub press_count: [u32; BUTTON_COUNT],
+    /// How many times each button went up during the last update
+    pub release_count: [u32; BUTTON_COUNT],
+    /// By `GamepadAxis`
+    pub analog_state: [f32; GamepadAxis::ALL.len()],
+    pub touchpads: [TouchpadState; MAX_TOUCHPADS],
+    /// Capacitive touch on the left and right thumbsticks
+    pub stick_touch: [bool; 2],
+    pub motion: MotionState,
 }
 
 impl ControllerState {
@@ -81,17 +291,87 @@ impl ControllerState {
         Self {
             status: ControllerStatus::Disconnected,
             sequence: 0,
-            digital_state: [false; GamepadButton::Max as usize],
-            digital_state_prev: [false; GamepadButton::Max as usize],
-            analog_state: [0.0; MAX_ANALOG],
+            digital_state: [false; BUTTON_COUNT],
+            digital_state_prev: [false; BUTTON_COUNT],
+            press_count: [0; BUTTON_COUNT],
+            release_count: [0; BUTTON_COUNT],
+            analog_state: [0.0; GamepadAxis::ALL.len()],
+            touchpads: [DEFAULT_TOUCHPAD_STATE; MAX_TOUCHPADS],
+            stick_touch: [false; 2],
+            motion: DEFAULT_MOTION_STATE,
+        }
+    }
+
+    pub fn is_down(&self, button: GamepadButton) -> bool {
+        self.digital_state[button as usize]
+    }
+
+    /// The button went down during the last update, even if it is already
+    /// back up
+    pub fn was_pressed(&self, button: GamepadButton) -> bool {
+        self.press_count[button as usize] > 0
+    }
+
+    /// The button went up during the last update, even if it is already
+    /// down again
+    pub fn was_released(&self, button: GamepadButton) -> bool {
+        self.release_count[button as usize] > 0
+    }
+
+    /// Value of a named axis
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.analog_state[axis as usize]
+    }
+
+    /// Start of an update: what was read so far becomes the previous state
+    /// and the edges are counted from zero again
+    pub fn begin_update(&mut self) {
+        self.digital_state_prev = self.digital_state;
+        self.press_count = [0; BUTTON_COUNT];
+        self.release_count = [0; BUTTON_COUNT];
+    }
+
+    /// Backends report named axes through here, triggers also press
+    /// `TriggerLeft` and `TriggerRight` past half way
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.analog_state[axis as usize] = value;
+        match axis {
+            GamepadAxis::LeftTrigger => self.set_button(GamepadButton::TriggerLeft, value >= 0.5),
+            GamepadAxis::RightTrigger => self.set_button(GamepadButton::TriggerRight, value >= 0.5),
+            _ => {}
         }
     }
+
+    /// Backends report every change of a button through here, as they read
+    /// it, so that edges between two updates are counted
+    pub fn set_button(&mut self, button: GamepadButton, pressed: bool) {
+        let index = button as usize;
+        if self.digital_state[index] != pressed {
+            self.digital_state[index] = pressed;
+            if pressed {
+                self.press_count[index] += 1;
+            } else {
+                self.release_count[index] += 1;
+            }
+        }
+    }
+}
+
+impl Default for ControllerState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 const DEFAULT_CONTROLLER_STATE: ControllerState = ControllerState {
     status: ControllerStatus::Disconnected,
     sequence: 0,
-    digital_state: [false; GamepadButton::Max as usize],
-    digital_state_prev: [false; GamepadButton::Max as usize],
-    analog_state: [0.0; MAX_ANALOG],
+    digital_state: [false; BUTTON_COUNT],
+    digital_state_prev: [false; BUTTON_COUNT],
+    press_count: [0; BUTTON_COUNT],
+    release_count: [0; BUTTON_COUNT],
+    analog_state: [0.0; GamepadAxis::ALL.len()],
+    touchpads: [DEFAULT_TOUCHPAD_STATE; MAX_TOUCHPADS],
+    stick_touch: [false; 2],
+    motion: DEFAULT_MOTION_STATE,
 };
@@ -1,126 +1,1545 @@
-#![allow(dead_code)]
-
 use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 
-use crate::GamepadButton;
+use crate::{
+    ControllerState, ControllerType, FaceStyle, GamepadAxis, GamepadButton, Guid, RawInput,
+};
 
-const MAPPINGS: &str = include_str!("mappings.txt");
+mod database;
+mod hash;
+pub mod wizard;
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Platform {
     Linux,
     Windows,
     Mac,
     Android,
+    #[allow(clippy::upper_case_acronyms)]
     IOS,
 }
 
-const MAX_BTNS: usize = 140;
+impl Platform {
+    /// The platform this crate is built for
+    pub fn current() -> Platform {
+        if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::Mac
+        } else if cfg!(target_os = "android") {
+            Platform::Android
+        } else if cfg!(target_os = "ios") {
+            Platform::IOS
+        } else {
+            Platform::Linux
+        }
+    }
 
-#[derive(Debug, Clone)]
+    /// Value of the `platform` field in SDL mappings
+    pub fn from_sdl_name(name: &str) -> Option<Platform> {
+        match name {
+            "Linux" => Some(Platform::Linux),
+            "Windows" => Some(Platform::Windows),
+            "Mac OS X" => Some(Platform::Mac),
+            "Android" => Some(Platform::Android),
+            "iOS" => Some(Platform::IOS),
+            _ => None,
+        }
+    }
+
+    pub fn to_sdl_name(self) -> &'static str {
+        match self {
+            Platform::Linux => "Linux",
+            Platform::Windows => "Windows",
+            Platform::Mac => "Mac OS X",
+            Platform::Android => "Android",
+            Platform::IOS => "iOS",
+        }
+    }
+}
+
+/// Part of an axis a mapping field covers: `a1`, `+a1` or `-a1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisRange {
+    Full,
+    Positive,
+    Negative,
+}
+
+impl AxisRange {
+    /// Value at rest and fully pushed. The negative half goes from 0.0 to
+    /// -1.0, so that pushing it further is still growing towards the end.
+    fn bounds(self) -> (f32, f32) {
+        match self {
+            AxisRange::Full => (-1.0, 1.0),
+            AxisRange::Positive => (0.0, 1.0),
+            AxisRange::Negative => (0.0, -1.0),
+        }
+    }
+}
+
+/// Left hand side of a mapping field: what the device reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// `b3`
+    Button(usize),
+    /// `a2`, `+a2`, `-a2`, `a2~`
+    Axis {
+        index: usize,
+        range: AxisRange,
+        inverted: bool,
+    },
+    /// `h0.4`, a bit of the `HAT_*` mask of hat 0
+    Hat { index: usize, mask: u8 },
+}
+
+pub const HAT_UP: u8 = 1;
+pub const HAT_RIGHT: u8 = 2;
+pub const HAT_DOWN: u8 = 4;
+pub const HAT_LEFT: u8 = 8;
+
+/// Right hand side of a mapping field: what the game sees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// `a`, `dpup`..
+    Button(GamepadButton),
+    /// `leftx`, `+leftx`, `-leftx`..
+    Axis { axis: GamepadAxis, range: AxisRange },
+}
+
+/// Button names of the SDL game controller mapping format
+const BUTTON_NAMES: [(&str, GamepadButton); 26] = [
+    ("a", GamepadButton::A),
+    ("b", GamepadButton::B),
+    ("x", GamepadButton::X),
+    ("y", GamepadButton::Y),
+    ("back", GamepadButton::Back),
+    ("guide", GamepadButton::Guide),
+    ("start", GamepadButton::Start),
+    ("leftstick", GamepadButton::ThumbLeft),
+    ("rightstick", GamepadButton::ThumbRight),
+    ("leftshoulder", GamepadButton::BumperLeft),
+    ("rightshoulder", GamepadButton::BumperRight),
+    ("dpup", GamepadButton::DpadUp),
+    ("dpdown", GamepadButton::DpadDown),
+    ("dpleft", GamepadButton::DpadLeft),
+    ("dpright", GamepadButton::DpadRight),
+    ("misc1", GamepadButton::Misc1),
+    ("misc2", GamepadButton::Misc2),
+    ("misc3", GamepadButton::Misc3),
+    ("misc4", GamepadButton::Misc4),
+    ("misc5", GamepadButton::Misc5),
+    ("misc6", GamepadButton::Misc6),
+    ("paddle1", GamepadButton::Paddle1),
+    ("paddle2", GamepadButton::Paddle2),
+    ("paddle3", GamepadButton::Paddle3),
+    ("paddle4", GamepadButton::Paddle4),
+    ("touchpad", GamepadButton::Touchpad),
+];
+
+/// Axis names of the SDL game controller mapping format
+const AXIS_NAMES: [(&str, GamepadAxis); 6] = [
+    ("leftx", GamepadAxis::LeftX),
+    ("lefty", GamepadAxis::LeftY),
+    ("rightx", GamepadAxis::RightX),
+    ("righty", GamepadAxis::RightY),
+    ("lefttrigger", GamepadAxis::LeftTrigger),
+    ("righttrigger", GamepadAxis::RightTrigger),
+];
+
+fn range_prefix(range: AxisRange) -> &'static str {
+    match range {
+        AxisRange::Full => "",
+        AxisRange::Positive => "+",
+        AxisRange::Negative => "-",
+    }
+}
+
+impl Target {
+    /// Every target of the SDL game controller mapping format
+    pub fn from_sdl_name(name: &str) -> Option<Target> {
+        let (range, name) = match name.as_bytes().first() {
+            Some(b'+') => (AxisRange::Positive, &name[1..]),
+            Some(b'-') => (AxisRange::Negative, &name[1..]),
+            _ => (AxisRange::Full, name),
+        };
+
+        if let Some((_, axis)) = AXIS_NAMES.iter().find(|(n, _)| *n == name) {
+            return Some(Target::Axis { axis: *axis, range });
+        }
+
+        // Only axes have halves
+        if range != AxisRange::Full {
+            return None;
+        }
+        BUTTON_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, button)| Target::Button(*button))
+    }
+
+    /// Name in the SDL game controller mapping format, `None` for buttons the
+    /// format has no name for. The digital triggers only exist as axes there.
+    pub fn to_sdl_name(self) -> Option<String> {
+        match self {
+            Target::Button(GamepadButton::TriggerLeft) => Some("lefttrigger".to_owned()),
+            Target::Button(GamepadButton::TriggerRight) => Some("righttrigger".to_owned()),
+            Target::Button(button) => BUTTON_NAMES
+                .iter()
+                .find(|(_, b)| *b == button)
+                .map(|(name, _)| name.to_string()),
+            Target::Axis { axis, range } => AXIS_NAMES
+                .iter()
+                .find(|(_, a)| *a == axis)
+                .map(|(name, _)| format!("{}{}", range_prefix(range), name)),
+        }
+    }
+
+    /// Value at rest and fully pushed, triggers only go from 0.0 to 1.0
+    fn bounds(self) -> (f32, f32) {
+        match self {
+            Target::Axis { axis, range } if axis.is_trigger() && range == AxisRange::Full => {
+                (0.0, 1.0)
+            }
+            Target::Axis { range, .. } => range.bounds(),
+            Target::Button(_) => (0.0, 1.0),
+        }
+    }
+}
+
+impl Source {
+    /// `b3`, `h0.4`, `a2`, `+a2`, `-a2`, `a2~`..
+    pub fn from_sdl_value(value: &str) -> Option<Source> {
+        let (range, value) = match value.as_bytes().first() {
+            Some(b'+') => (AxisRange::Positive, &value[1..]),
+            Some(b'-') => (AxisRange::Negative, &value[1..]),
+            _ => (AxisRange::Full, value),
+        };
+
+        let source = match value.as_bytes().first() {
+            Some(b'a') => {
+                let (index, inverted) = match value.strip_suffix('~') {
+                    Some(index) => (&index[1..], true),
+                    None => (&value[1..], false),
+                };
+                Source::Axis {
+                    index: index.parse().ok()?,
+                    range,
+                    inverted,
+                }
+            }
+            Some(b'b') => Source::Button(value[1..].parse().ok()?),
+            Some(b'h') => {
+                let (index, mask) = value[1..].split_once('.')?;
+                Source::Hat {
+                    index: index.parse().ok()?,
+                    mask: mask.parse().ok()?,
+                }
+            }
+            _ => return None,
+        };
+
+        // Only axes have halves
+        match source {
+            Source::Axis { .. } => Some(source),
+            _ if range == AxisRange::Full => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Value in the SDL game controller mapping format
+    pub fn to_sdl_value(self) -> String {
+        match self {
+            Source::Button(index) => format!("b{}", index),
+            Source::Axis {
+                index,
+                range,
+                inverted,
+            } => format!(
+                "{}a{}{}",
+                range_prefix(range),
+                index,
+                if inverted { "~" } else { "" }
+            ),
+            Source::Hat { index, mask } => format!("h{}.{}", index, mask),
+        }
+    }
+
+    /// Value at rest and fully pushed, in the -1.0..1.0 range backends report
+    fn bounds(self) -> (f32, f32) {
+        match self {
+            Source::Axis {
+                range, inverted, ..
+            } => {
+                let (min, max) = range.bounds();
+                if inverted {
+                    (max, min)
+                } else {
+                    (min, max)
+                }
+            }
+            _ => (0.0, 1.0),
+        }
+    }
+}
+
+/// One `target:source` field of a mapping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+    pub target: Target,
+    pub source: Source,
+}
+
+impl Binding {
+    /// Whether an axis value is in the part of the axis the source covers
+    fn matches(&self, value: f32) -> bool {
+        let (input_min, input_max) = self.source.bounds();
+        if input_min < input_max {
+            value >= input_min && value <= input_max
+        } else {
+            value <= input_min && value >= input_max
+        }
+    }
+
+    /// Sets the target from a source value the binding matches, like SDL's
+    /// `HandleJoystickAxis` does with the binding it picked
+    fn apply(&self, state: &mut ControllerState, value: f32) {
+        let (input_min, input_max) = self.source.bounds();
+        match self.target {
+            Target::Axis { axis, .. } => {
+                let (output_min, output_max) = self.target.bounds();
+                let normalized = (value - input_min) / (input_max - input_min);
+                state.set_axis(axis, output_min + normalized * (output_max - output_min));
+            }
+            Target::Button(button) => {
+                let threshold = input_min + (input_max - input_min) / 2.;
+                let pressed = if input_max < input_min {
+                    value <= threshold
+                } else {
+                    value >= threshold
+                };
+                state.set_button(button, pressed);
+            }
+        }
+    }
+
+    /// Puts the target back at rest
+    fn reset(&self, state: &mut ControllerState) {
+        match self.target {
+            Target::Axis { axis, .. } => state.set_axis(axis, 0.0),
+            Target::Button(button) => state.set_button(button, false),
+        }
+    }
+
+    fn same_target(&self, other: &Binding) -> bool {
+        match (self.target, other.target) {
+            (Target::Axis { axis: a, .. }, Target::Axis { axis: b, .. }) => a == b,
+            (Target::Button(a), Target::Button(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// `hint:!NAME:=1`, the mapping only applies when the boolean hint `NAME` is
+/// set, or not with `!`. `default` is its value when nobody set it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintCondition {
+    pub name: String,
+    pub negate: bool,
+    pub default: bool,
+}
+
+impl HintCondition {
+    fn from_sdl_value(value: &str) -> HintCondition {
+        let (negate, value) = match value.strip_prefix('!') {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+        let (name, default) = match value.split_once(":=") {
+            Some((name, default)) => (name, default.trim().parse().unwrap_or(0) != 0),
+            None => (value, false),
+        };
+        HintCondition {
+            name: name.to_owned(),
+            negate,
+            default,
+        }
+    }
+
+    fn to_sdl_value(&self) -> String {
+        format!(
+            "{}{}:={}",
+            if self.negate { "!" } else { "" },
+            self.name,
+            self.default as u8
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Mapping {
-    pub guid: String,
+    /// With the CRC of the `crc` field in it, if there is one
+    pub guid: Guid,
     pub name: String,
-    pub platform: Platform,
-    pub buttons: [GamepadButton; MAX_BTNS],
+    /// `None` when the mapping has no `platform` field
+    pub platform: Option<Platform>,
+    pub bindings: Vec<Binding>,
+    /// What is printed on the face buttons, from the `face` field
+    pub face: Option<FaceStyle>,
+    pub hint: Option<HintCondition>,
+    /// Android API levels the mapping applies to, from `sdk>=` and `sdk<=`
+    pub sdk_min: Option<u32>,
+    pub sdk_max: Option<u32>,
+    /// Fields with a name this crate does not know, written back as they are
+    pub unknown_fields: Vec<(String, String)>,
 }
 
+/// The binding each axis of a device matched last in its mapping, see
+/// `Mapping::set_axis`. Backends keep one per device, and start over when
+/// the mapping changes.
+#[derive(Debug, Clone, Default)]
+pub struct AxisMatches(Vec<Option<usize>>);
+
 impl Mapping {
-    pub fn new(guid: &str) -> Mapping {
+    pub fn new(guid: Guid) -> Mapping {
         use GamepadButton::*;
 
-        let mut buttons = [Unknown; MAX_BTNS];
-        buttons[0] = A;
-        buttons[1] = B;
-        buttons[2] = X;
-        buttons[3] = Y;
-        buttons[4] = BumperLeft;
-        buttons[5] = BumperRight;
-        buttons[6] = ThumbLeft;
-        buttons[7] = ThumbRight;
-        buttons[8] = Back;
-        buttons[9] = Select;
-        buttons[10] = Start;
-        buttons[11] = DpadUp;
-        buttons[12] = DpadDown;
-        buttons[13] = DpadRight;
-        buttons[14] = DpadLeft;
+        let mut bindings = vec![];
+        for (i, button) in [
+            A,
+            B,
+            X,
+            Y,
+            BumperLeft,
+            BumperRight,
+            ThumbLeft,
+            ThumbRight,
+            Back,
+            Guide,
+            Start,
+            DpadUp,
+            DpadDown,
+            DpadRight,
+            DpadLeft,
+        ]
+        .iter()
+        .enumerate()
+        {
+            bindings.push(Binding {
+                target: Target::Button(*button),
+                source: Source::Button(i),
+            });
+        }
+
+        // Keep the order the device reports its axes in
+        for (i, axis) in GamepadAxis::ALL.iter().enumerate() {
+            bindings.push(Binding {
+                target: Target::Axis {
+                    axis: *axis,
+                    range: AxisRange::Full,
+                },
+                source: Source::Axis {
+                    index: i,
+                    range: AxisRange::Full,
+                    inverted: false,
+                },
+            });
+        }
+
+        for (mask, button) in [
+            (HAT_UP, DpadUp),
+            (HAT_RIGHT, DpadRight),
+            (HAT_DOWN, DpadDown),
+            (HAT_LEFT, DpadLeft),
+        ]
+        .iter()
+        {
+            bindings.push(Binding {
+                target: Target::Button(*button),
+                source: Source::Hat {
+                    index: 0,
+                    mask: *mask,
+                },
+            });
+        }
 
         Mapping {
-            guid: guid.to_owned(),
+            guid,
             name: "unknown".to_string(),
-            platform: Platform::Linux,
-            buttons,
+            platform: None,
+            bindings,
+            face: None,
+            hint: None,
+            sdk_min: None,
+            sdk_max: None,
+            unknown_fields: vec![],
+        }
+    }
+
+    /// What is printed on the face buttons of a `controller_type` controller
+    /// as this mapping names them: its `face` field, else the labels
+    /// themselves for entries chosen with
+    /// `hint:SDL_GAMECONTROLLER_USE_BUTTON_LABELS`, else the labels of the
+    /// family
+    pub fn face_style(&self, controller_type: ControllerType) -> FaceStyle {
+        let by_label = self.hint.as_ref().is_some_and(|hint| {
+            hint.name == "SDL_GAMECONTROLLER_USE_BUTTON_LABELS" && !hint.negate
+        });
+        match self.face {
+            Some(face) => face,
+            None if by_label => FaceStyle::Abxy,
+            None => controller_type.face_style(),
+        }
+    }
+
+    /// Whether the mapping has conditions on hints or SDK versions
+    pub fn is_conditional(&self) -> bool {
+        self.hint.is_some() || self.sdk_min.is_some() || self.sdk_max.is_some()
+    }
+
+    /// Whether the conditions of the mapping hold, the way SDL checks them.
+    /// `hint` gives the value of a hint from its name and default value.
+    /// Without an `sdk` version, off Android, SDK ranges are ignored.
+    pub fn applies(&self, hint: impl Fn(&str, bool) -> bool, sdk: Option<u32>) -> bool {
+        if let Some(condition) = &self.hint {
+            if hint(&condition.name, condition.default) == condition.negate {
+                return false;
+            }
+        }
+        if let Some(sdk) = sdk {
+            if self.sdk_min.is_some_and(|min| sdk < min)
+                || self.sdk_max.is_some_and(|max| sdk > max)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Buttons a device with that many buttons, axes and hats ends up with,
+    /// the digital side of its triggers included
+    pub fn buttons(&self, buttons: usize, axes: usize, hats: usize) -> Vec<GamepadButton> {
+        let mut res = vec![];
+        for binding in &self.bindings {
+            let present = match binding.source {
+                Source::Button(index) => index < buttons,
+                Source::Axis { index, .. } => index < axes,
+                Source::Hat { index, .. } => index < hats,
+            };
+            let button = match binding.target {
+                Target::Button(button) => button,
+                Target::Axis {
+                    axis: GamepadAxis::LeftTrigger,
+                    ..
+                } => GamepadButton::TriggerLeft,
+                Target::Axis {
+                    axis: GamepadAxis::RightTrigger,
+                    ..
+                } => GamepadButton::TriggerRight,
+                Target::Axis { .. } => continue,
+            };
+            if present && !res.contains(&button) {
+                res.push(button);
+            }
+        }
+        res
+    }
+
+    /// Number of `ControllerState::analog_state` values a device with that
+    /// many buttons, axes and hats fills
+    pub fn analog_count(&self, buttons: usize, axes: usize, hats: usize) -> usize {
+        let mapped = self.bindings.iter().filter_map(|binding| {
+            let present = match binding.source {
+                Source::Button(index) => index < buttons,
+                Source::Axis { index, .. } => index < axes,
+                Source::Hat { index, .. } => index < hats,
+            };
+            match binding.target {
+                Target::Axis { axis, .. } if present => Some(axis as usize),
+                _ => None,
+            }
+        });
+        mapped.map(|slot| slot + 1).max().unwrap_or(0)
+    }
+
+    /// Report a button of the device going down or up
+    pub fn set_button(&self, state: &mut ControllerState, index: usize, pressed: bool) {
+        for binding in &self.bindings {
+            if binding.source == Source::Button(index) {
+                binding.apply(state, if pressed { 1.0 } else { 0.0 });
+            }
+        }
+    }
+
+    /// Report a new value, in -1.0..1.0, of an axis of the device. Like
+    /// SDL, the first binding of the axis whose part holds the value sets
+    /// its target, and the target set by the binding the axis matched
+    /// before goes back to rest, so that `+a3` and `-a3` bound to two
+    /// triggers release one when the axis swings to the other side.
+    pub fn set_axis(
+        &self,
+        state: &mut ControllerState,
+        matches: &mut AxisMatches,
+        index: usize,
+        value: f32,
+    ) {
+        let found = self.bindings.iter().position(|binding| {
+            matches!(binding.source, Source::Axis { index: i, .. } if i == index)
+                && binding.matches(value)
+        });
+
+        let matches = &mut matches.0;
+        if matches.len() <= index {
+            matches.resize(index + 1, None);
+        }
+        let last = matches[index].and_then(|last| self.bindings.get(last));
+        if let Some(last) = last {
+            if found.is_none_or(|found| !self.bindings[found].same_target(last)) {
+                last.reset(state);
+            }
+        }
+        if let Some(found) = found {
+            self.bindings[found].apply(state, value);
+        }
+        matches[index] = found;
+    }
+
+    /// Bring `state` to what `raw` gives through the mapping, once a device
+    /// switched to it. Buttons and axes change through `set_button` and
+    /// `set_axis` of the state, so that what the previous mapping held down
+    /// is released like any other button. `matches` starts over.
+    pub fn apply_raw(
+        &self,
+        raw: &RawInput,
+        state: &mut ControllerState,
+        matches: &mut AxisMatches,
+    ) {
+        let mut mapped = ControllerState::new();
+        *matches = AxisMatches::default();
+        for (index, pressed) in raw.buttons.iter().enumerate() {
+            self.set_button(&mut mapped, index, *pressed);
+        }
+        for (index, value) in raw.axes.iter().enumerate() {
+            self.set_axis(&mut mapped, matches, index, *value);
+        }
+        for (index, bits) in raw.hats.iter().enumerate() {
+            self.set_hat(&mut mapped, index, *bits);
+        }
+
+        // Axes first, the triggers press buttons
+        for axis in GamepadAxis::ALL {
+            state.set_axis(axis, mapped.axis(axis));
+        }
+        for button in GamepadButton::ALL {
+            state.set_button(button, mapped.is_down(button));
+        }
+    }
+
+    /// Report a new position, as `HAT_*` bits, of a hat of the device
+    pub fn set_hat(&self, state: &mut ControllerState, index: usize, bits: u8) {
+        for binding in &self.bindings {
+            if let Source::Hat { index: i, mask } = binding.source {
+                if i == index {
+                    binding.apply(state, if bits & mask != 0 { 1.0 } else { 0.0 });
+                }
+            }
+        }
+    }
+}
+
+/// The canonical SDL game controller mapping string: GUID, name, the fields
+/// sorted by name, unknown ones included, the conditions and the platform.
+/// Bindings to buttons the format has no name for are left out.
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields: Vec<(String, String)> = self
+            .bindings
+            .iter()
+            .filter_map(|binding| {
+                let name = binding.target.to_sdl_name()?;
+                Some((name, binding.source.to_sdl_value()))
+            })
+            .chain(
+                self.face
+                    .map(|face| ("face".to_owned(), face.to_sdl_name().to_owned())),
+            )
+            .chain(self.unknown_fields.iter().cloned())
+            .collect();
+        // `+leftx` and `-leftx` next to `leftx`
+        fields.sort_by(|(a, _), (b, _)| {
+            a.trim_start_matches(['+', '-'])
+                .cmp(b.trim_start_matches(['+', '-']))
+                .then(a.cmp(b))
+        });
+
+        // Like SDL, the CRC goes in its own field
+        write!(f, "{},{},", self.guid.without_crc(), self.name)?;
+        for (name, value) in fields {
+            write!(f, "{}:{},", name, value)?;
+        }
+        if self.guid.crc() != 0 {
+            write!(f, "crc:{:04x},", self.guid.crc())?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "hint:{},", hint.to_sdl_value())?;
+        }
+        if let Some(sdk) = self.sdk_min {
+            write!(f, "sdk>=:{},", sdk)?;
+        }
+        if let Some(sdk) = self.sdk_max {
+            write!(f, "sdk<=:{},", sdk)?;
+        }
+        if let Some(platform) = self.platform {
+            write!(f, "platform:{},", platform.to_sdl_name())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The line has no `,` after the GUID
+    MissingName,
+    InvalidGuid(String),
+    /// A field is not `name:value`
+    MissingColon,
+    UnknownPlatform(String),
+    /// The value of a field is not `bN`, `aN`, `hN.M` or a variant of those
+    InvalidSource(String),
+    /// A target that only exists as a full axis or button got a `+` or `-`
+    InvalidTarget(String),
+    /// A `crc`, `sdk>=` or `sdk<=` value that is not a number
+    InvalidCondition(String),
+}
+
+/// Why a line of a mappings file was rejected, `line` and `column` start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::MissingName => write!(f, "missing controller name"),
+            ParseErrorKind::InvalidGuid(guid) => write!(f, "invalid GUID {:?}", guid),
+            ParseErrorKind::MissingColon => write!(f, "field is not `name:value`"),
+            ParseErrorKind::UnknownPlatform(name) => write!(f, "unknown platform {:?}", name),
+            ParseErrorKind::InvalidSource(value) => write!(f, "invalid input {:?}", value),
+            ParseErrorKind::InvalidTarget(name) => write!(f, "invalid output {:?}", name),
+            ParseErrorKind::InvalidCondition(value) => write!(f, "invalid condition {:?}", value),
         }
     }
 }
 
-pub type MappingsMap = HashMap<String, Mapping>;
+impl std::error::Error for ParseError {}
+
+/// Parse one line of the SDL game controller mapping format.
+///
+/// Fields with a target this crate does not know about are not bindings,
+/// like SDL skips them, so mappings written for newer versions of the format
+/// still load. They are kept in `unknown_fields`. Errors report `line` as 1.
+pub fn parse_mapping(line: &str) -> Result<Mapping, ParseError> {
+    let error = |offset: usize, kind| ParseError {
+        line: 1,
+        column: line[..offset].chars().count() + 1,
+        kind,
+    };
+
+    let (guid, rest) = line
+        .split_once(',')
+        .ok_or_else(|| error(line.len(), ParseErrorKind::MissingName))?;
+    let guid = guid
+        .parse()
+        .map_err(|_| error(0, ParseErrorKind::InvalidGuid(guid.to_owned())))?;
+    let (name, fields) = rest.split_once(',').unwrap_or((rest, ""));
+
+    let mut mapping = Mapping::new(guid);
+    mapping.name = name.to_owned();
+    mapping.bindings.clear();
+
+    let mut offset = line.len() - fields.len();
+    for field in fields.split(',') {
+        let start = offset;
+        offset += field.len() + 1;
+
+        if field.is_empty() {
+            continue;
+        }
+        let (key, value) = field
+            .split_once(':')
+            .ok_or_else(|| error(start, ParseErrorKind::MissingColon))?;
+        let value_start = start + key.len() + 1;
+
+        if key == "platform" {
+            let platform = Platform::from_sdl_name(value).ok_or_else(|| {
+                error(
+                    value_start,
+                    ParseErrorKind::UnknownPlatform(value.to_owned()),
+                )
+            })?;
+            mapping.platform = Some(platform);
+            continue;
+        }
+
+        let invalid_condition = || {
+            error(
+                value_start,
+                ParseErrorKind::InvalidCondition(value.to_owned()),
+            )
+        };
+        match key {
+            "crc" => {
+                let crc = u16::from_str_radix(value, 16).map_err(|_| invalid_condition())?;
+                mapping.guid = mapping.guid.with_crc(crc);
+                continue;
+            }
+            "hint" => {
+                mapping.hint = Some(HintCondition::from_sdl_value(value));
+                continue;
+            }
+            // Face styles of newer versions of the format stay unknown fields
+            "face" if FaceStyle::from_sdl_name(value).is_some() => {
+                mapping.face = FaceStyle::from_sdl_name(value);
+                continue;
+            }
+            "sdk>=" => {
+                mapping.sdk_min = Some(value.parse().map_err(|_| invalid_condition())?);
+                continue;
+            }
+            "sdk<=" => {
+                mapping.sdk_max = Some(value.parse().map_err(|_| invalid_condition())?);
+                continue;
+            }
+            _ => {}
+        }
+
+        let target = match Target::from_sdl_name(key) {
+            Some(target) => target,
+            None if Target::from_sdl_name(key.trim_start_matches(['+', '-'])).is_some() => {
+                return Err(error(start, ParseErrorKind::InvalidTarget(key.to_owned())))
+            }
+            None => {
+                mapping
+                    .unknown_fields
+                    .push((key.to_owned(), value.to_owned()));
+                continue;
+            }
+        };
+        let source = Source::from_sdl_value(value)
+            .ok_or_else(|| error(value_start, ParseErrorKind::InvalidSource(value.to_owned())))?;
+        mapping.bindings.push(Binding { target, source });
+    }
+
+    Ok(mapping)
+}
 
-// this should be a (proc?) macro
-pub fn read_mappings_file(target_platform: Platform) -> MappingsMap {
-    let mut mappings_map = HashMap::new();
+/// Parse a whole mappings file, one mapping per line, `#` starts a comment.
+///
+/// Lines that fail to parse are left out of the mappings and reported in the
+/// errors, in file order.
+pub fn parse_mappings(text: &str) -> (Vec<Mapping>, Vec<ParseError>) {
+    let mut mappings = vec![];
+    let mut errors = vec![];
 
-    for line in MAPPINGS.lines() {
-        if line.starts_with("#") || line.is_empty() {
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with('#') || line.trim().is_empty() {
             continue;
         }
 
-        let mut tokens = line.split(',');
+        match parse_mapping(line) {
+            Ok(mapping) => mappings.push(mapping),
+            Err(error) => errors.push(ParseError {
+                line: index + 1,
+                ..error
+            }),
+        }
+    }
+
+    (mappings, errors)
+}
+
+/// Where the mapping of a controller comes from. Entries of a later layer
+/// win over the ones of earlier layers, whatever order they were added in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MappingLayer {
+    /// No entry and nothing to go by, the default layout
+    Default,
+    /// No entry, made from what the device reports, like the Linux evdev
+    /// codes of gamepads
+    Automatic,
+    /// The database built into the crate
+    Database,
+    /// A database shipped with the game, `load_mappings_from_file`
+    Game,
+    /// The player's own file, `load_user_mappings_from_file`
+    User,
+    /// `SDL_GAMECONTROLLERCONFIG_FILE` and `SDL_GAMECONTROLLERCONFIG`
+    Environment,
+    /// `add_mapping`
+    Runtime,
+    /// Not mapped, the backend knows the layout itself, like XInput or the
+    /// Steam Deck
+    Native,
+}
+
+/// Which entry a controller got its mapping from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingOrigin {
+    pub layer: MappingLayer,
+    /// The file of the entry, `None` for the other layers
+    pub path: Option<PathBuf>,
+    /// Line of the entry in its file, in `SDL_GAMECONTROLLERCONFIG` or in
+    /// the bundled database, starting at 1
+    pub line: Option<usize>,
+}
+
+impl MappingOrigin {
+    pub fn new(layer: MappingLayer) -> MappingOrigin {
+        MappingOrigin {
+            layer,
+            path: None,
+            line: None,
+        }
+    }
+
+    /// No entry was found for the controller
+    pub fn is_fallback(&self) -> bool {
+        matches!(self.layer, MappingLayer::Default | MappingLayer::Automatic)
+    }
+}
+
+impl Default for MappingOrigin {
+    fn default() -> MappingOrigin {
+        MappingOrigin::new(MappingLayer::Default)
+    }
+}
+
+impl fmt::Display for MappingOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.layer)?;
+        if let Some(path) = &self.path {
+            write!(f, " {}", path.display())?;
+        }
+        if let Some(line) = self.line {
+            write!(f, " line {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    mapping: Mapping,
+    origin: MappingOrigin,
+}
+
+/// The entries of a database entry, bad lines left out
+fn database_entries(lines: &[(usize, &str)]) -> impl DoubleEndedIterator<Item = Entry> {
+    lines
+        .iter()
+        .filter_map(|(line, text)| {
+            Some(Entry {
+                mapping: parse_mapping(text).ok()?,
+                origin: MappingOrigin {
+                    line: Some(*line),
+                    ..MappingOrigin::new(MappingLayer::Database)
+                },
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Mappings a context knows about, in layers over the bundled database.
+/// Backends look up the mapping of their devices with `get`.
+pub struct Mappings {
+    platform: Platform,
+    /// Per layer and GUID, an unconditional entry and the conditional ones
+    /// added after it, in order
+    added: HashMap<(MappingLayer, Guid), Vec<Entry>>,
+    /// Hints of the `SDL_*` environment variables when the mappings were
+    /// made, and the ones of `set_hint` over them
+    hints: HashMap<String, bool>,
+}
+
+/// The boolean hints among environment variables `vars`, the `SDL_*` ones,
+/// read like SDL reads them
+fn parse_hints(vars: impl Iterator<Item = (OsString, OsString)>) -> HashMap<String, bool> {
+    vars.filter_map(|(name, value)| {
+        let name = name.into_string().ok()?;
+        let value = value.into_string().ok()?;
+        if !name.starts_with("SDL_") || value.is_empty() {
+            return None;
+        }
+        Some((name, value != "0" && !value.eq_ignore_ascii_case("false")))
+    })
+    .collect()
+}
+
+impl Mappings {
+    /// The bundled database, and the mappings and hints of the SDL
+    /// environment variables
+    pub(crate) fn new(platform: Platform) -> Mappings {
+        Mappings::with_hints(platform, parse_hints(std::env::vars_os()))
+    }
 
-        let guid = tokens.next().unwrap();
-        let name = tokens.next().unwrap();
+    /// Like `new`, with `hints` in place of the ones of the environment
+    fn with_hints(platform: Platform, hints: HashMap<String, bool>) -> Mappings {
+        let mut mappings = Mappings {
+            platform,
+            added: HashMap::new(),
+            hints,
+        };
+        mappings.add_env_mappings();
+        mappings
+    }
+
+    /// The mapping for a device, looked up like SDL does: a mapping for its
+    /// exact GUID, then one without name CRC, then one for another version
+    /// of the device. For each, the highest layer with a mapping whose
+    /// conditions hold wins, and in that layer the last such mapping. The
+    /// mapping found gets the GUID of the device.
+    pub fn get(&self, guid: Guid) -> Option<(Mapping, MappingOrigin)> {
+        let mut entry = self
+            .find(guid)
+            .or_else(|| self.find(guid.without_crc()))
+            .or_else(|| {
+                let added = self
+                    .added
+                    .iter()
+                    .filter(|((_, g), _)| g.matches(&guid, false))
+                    .filter_map(|((layer, _), entries)| {
+                        Some((*layer, self.choose(entries.iter().cloned())?))
+                    })
+                    .max_by_key(|(layer, _)| *layer)
+                    .map(|(_, entry)| entry);
+                added.or_else(|| {
+                    // Entries without CRC match any
+                    let without_crc = Some(guid.without_crc()).filter(|_| guid.crc() != 0);
+                    database::lookup_any_version(guid)
+                        .chain(
+                            without_crc
+                                .into_iter()
+                                .flat_map(database::lookup_any_version),
+                        )
+                        .find_map(|lines| self.choose(database_entries(lines)))
+                })
+            })?;
+        entry.mapping.guid = guid;
+        Some((entry.mapping, entry.origin))
+    }
 
-        let mut mapping = Mapping::new(guid);
-        mapping.name = name.to_owned();
+    fn find(&self, guid: Guid) -> Option<Entry> {
+        use MappingLayer::*;
 
-        while let Some(pair) = tokens.next() {
-            if pair.is_empty() {
-                break;
+        for layer in [Runtime, Environment, User, Game] {
+            if let Some(entry) = self
+                .added
+                .get(&(layer, guid))
+                .and_then(|entries| self.choose(entries.iter().cloned()))
+            {
+                return Some(entry);
             }
-            let mut pair = pair.split(':');
-            let key = pair.next().unwrap();
-            let value = pair.next().unwrap();
+        }
+        database::lookup(guid).and_then(|lines| self.choose(database_entries(lines)))
+    }
 
-            match key.as_ref() {
-                "platform" if value == "Windows" => mapping.platform = Platform::Windows,
-                "platform" if value == "Linux" => mapping.platform = Platform::Linux,
-                "platform" if value == "Mac OS X" => mapping.platform = Platform::Mac,
-                "platform" if value == "Android" => mapping.platform = Platform::Android,
-                "platform" if value == "iOS" => mapping.platform = Platform::IOS,
-                "platform" => panic!("{:?}", value),
-                "a" => {
-                    let ix: usize = value[1..].parse().unwrap();
-                    mapping.buttons[ix] = GamepadButton::A;
-                }
-                "b" => {
-                    let ix: usize = value[1..].parse().unwrap();
-                    mapping.buttons[ix] = GamepadButton::B;
-                }
-                "x" => {
-                    let ix: usize = value[1..].parse().unwrap();
-                    mapping.buttons[ix] = GamepadButton::X;
-                }
-                "y" => {
-                    let ix: usize = value[1..].parse().unwrap();
-                    mapping.buttons[ix] = GamepadButton::Y;
-                }
-                "back" => {
-                    let ix: usize = value[1..].parse().unwrap();
-                    mapping.buttons[ix] = GamepadButton::Back;
+    /// The last of `entries` whose conditions hold. There is no Android
+    /// backend, so SDK ranges never rule a mapping out.
+    fn choose(&self, entries: impl DoubleEndedIterator<Item = Entry>) -> Option<Entry> {
+        entries.rev().find(|entry| {
+            entry
+                .mapping
+                .applies(|name, default| self.hint(name, default), None)
+        })
+    }
+
+    /// Value of a boolean hint: the one set with `set_hint`, else the one of
+    /// the environment variable of the same name, else `default`
+    fn hint(&self, name: &str, default: bool) -> bool {
+        self.hints.get(name).copied().unwrap_or(default)
+    }
+
+    pub(crate) fn set_hint(&mut self, name: &str, value: bool) {
+        self.hints.insert(name.to_owned(), value);
+    }
+
+    /// Add one mapping in the SDL format to the runtime layer. A mapping for
+    /// another platform is not an error, it is left out. Returns the GUID of
+    /// the added mapping.
+    pub(crate) fn add_mapping(&mut self, line: &str) -> Result<Option<Guid>, ParseError> {
+        self.add_line(line, MappingOrigin::new(MappingLayer::Runtime))
+    }
+
+    fn add_line(&mut self, line: &str, origin: MappingOrigin) -> Result<Option<Guid>, ParseError> {
+        let mapping = parse_mapping(line)?;
+        if mapping.platform.unwrap_or(self.platform) != self.platform {
+            return Ok(None);
+        }
+
+        let guid = mapping.guid;
+        self.insert(Entry { mapping, origin });
+        Ok(Some(guid))
+    }
+
+    /// Add the mappings for this platform of a mappings file to `layer`.
+    /// Like SDL does for files, entries without a `platform` field are left
+    /// out.
+    pub(crate) fn add_mappings_from_file<P: AsRef<Path>>(
+        &mut self,
+        layer: MappingLayer,
+        path: P,
+    ) -> io::Result<Vec<ParseError>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let mut errors = vec![];
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            match parse_mapping(line) {
+                Ok(mapping) if mapping.platform == Some(self.platform) => {
+                    self.insert(Entry {
+                        mapping,
+                        origin: MappingOrigin {
+                            layer,
+                            path: Some(path.to_owned()),
+                            line: Some(index + 1),
+                        },
+                    });
                 }
-                "start" => {
-                    let ix: usize = value[1..].parse().unwrap();
-                    mapping.buttons[ix] = GamepadButton::Start;
+                Ok(_) => {}
+                Err(error) => errors.push(ParseError {
+                    line: index + 1,
+                    ..error
+                }),
+            }
+        }
+        Ok(errors)
+    }
+
+    /// An unconditional entry replaces the ones for its GUID in its layer, a
+    /// conditional one goes after them
+    fn insert(&mut self, entry: Entry) {
+        let key = (entry.origin.layer, entry.mapping.guid);
+        let entries = self.added.entry(key).or_default();
+        if !entry.mapping.is_conditional() {
+            entries.clear();
+        }
+        entries.push(entry);
+    }
+
+    /// Add the mappings of the `SDL_GAMECONTROLLERCONFIG_FILE` file and of
+    /// the `SDL_GAMECONTROLLERCONFIG` variable, one per line, in that order
+    /// like SDL. Bad entries are skipped.
+    fn add_env_mappings(&mut self) {
+        if let Some(path) = std::env::var_os("SDL_GAMECONTROLLERCONFIG_FILE") {
+            let _ = self.add_mappings_from_file(MappingLayer::Environment, path);
+        }
+
+        if let Ok(text) = std::env::var("SDL_GAMECONTROLLERCONFIG") {
+            for (index, line) in text.lines().enumerate() {
+                let line = line.trim_end_matches('\r');
+                if !line.starts_with('#') && !line.trim().is_empty() {
+                    let origin = MappingOrigin {
+                        line: Some(index + 1),
+                        ..MappingOrigin::new(MappingLayer::Environment)
+                    };
+                    let _ = self.add_line(line, origin);
                 }
-                _ => {}
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPINGS: &str = include_str!("mappings.txt");
+
+    #[test]
+    fn bundled_mappings_round_trip() {
+        let (_, errors) = parse_mappings(MAPPINGS);
+        assert_eq!(errors, vec![]);
+
+        // The fields after the GUID and the name, in any order
+        let fields = |line: &str| {
+            let mut fields: Vec<String> = line
+                .split(',')
+                .skip(2)
+                .filter(|field| !field.is_empty())
+                .map(str::to_owned)
+                .collect();
+            fields.sort();
+            fields
+        };
+        for line in MAPPINGS.lines() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let mapping = parse_mapping(line).unwrap();
+            let text = mapping.to_string();
+
+            let guid = |line: &str| line.split(',').next()?.parse::<Guid>().ok();
+            assert_eq!(guid(&text), guid(line), "{}", line);
+            assert_eq!(text.split(',').nth(1), line.split(',').nth(1));
+            assert_eq!(fields(&text), fields(line), "{}", line);
+            assert_eq!(parse_mapping(&text).unwrap().to_string(), text);
+        }
+    }
 
-        if target_platform == mapping.platform {
-            mappings_map.insert(guid.to_owned(), mapping);
+    #[test]
+    #[cfg(feature = "mapping-db")]
+    fn database_lookup() {
+        for (guid, entry) in database::entries() {
+            assert_eq!(database::lookup(guid), Some(entry));
+            assert!(
+                database::lookup_any_version(guid.without_version()).any(|other| other == entry)
+            );
+            assert!(entry[0].1.starts_with(&guid.to_string()));
         }
+        assert_eq!(database::lookup(Guid::default()), None);
+        assert_eq!(database::lookup_any_version(Guid::default()).count(), 0);
+    }
+
+    #[test]
+    fn canonical_field_order() {
+        let mut mapping =
+            parse_mapping("03000000de2800000512000001000000,Pad,x:b2,-leftx:a0,future:1,face:axby,a:b0,leftx:a3~,+leftx:+a1,platform:Linux")
+                .unwrap();
+        assert_eq!(mapping.face, Some(FaceStyle::Axby));
+        assert_eq!(
+            mapping.unknown_fields,
+            [("future".to_owned(), "1".to_owned())]
+        );
+        assert_eq!(
+            mapping.to_string(),
+            "03000000de2800000512000001000000,Pad,a:b0,face:axby,future:1,+leftx:+a1,-leftx:a0,leftx:a3~,x:b2,platform:Linux,"
+        );
+
+        // Made by a `MappingSession` asking for the digital trigger
+        mapping.bindings = vec![Binding {
+            target: Target::Button(GamepadButton::TriggerLeft),
+            source: Source::Button(6),
+        }];
+        mapping.face = None;
+        mapping.unknown_fields.clear();
+        assert_eq!(
+            mapping.to_string(),
+            "03000000de2800000512000001000000,Pad,lefttrigger:b6,platform:Linux,"
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let labeled = "03000000de2800003412000001000000,Pro,a:b1,b:b0,crc:1234,hint:!SDL_TEST_LABELS:=1,sdk>=:21,platform:Linux,";
+        let mapping = parse_mapping(labeled).unwrap();
+        assert_eq!(mapping.guid.crc(), 0x1234);
+        assert_eq!(mapping.to_string(), labeled);
+        assert!(!mapping.applies(|_, default| default, None));
+        assert!(mapping.applies(|_, _| false, None));
+        assert!(!mapping.applies(|_, _| false, Some(20)));
+
+        let mut mappings = Mappings::new(Platform::Linux);
+        let guid = mapping.guid;
+        mappings
+            .add_mapping("03000000de2800003412000001000000,Pro,a:b0,b:b1,crc:1234,platform:Linux")
+            .unwrap();
+        mappings.add_mapping(labeled).unwrap();
+        assert_eq!(mappings.get(guid).unwrap().0.hint, None);
+        mappings.set_hint("SDL_TEST_LABELS", false);
+        assert_eq!(mappings.get(guid).unwrap().0, Mapping { guid, ..mapping });
+        // The CRC of the mapping has to match the one of the device
+        assert!(mappings.get(guid.with_crc(0x4321)).is_none());
+    }
+
+    #[test]
+    fn face_labels() {
+        use GamepadButton::*;
+
+        let switch = ControllerType::SwitchPro;
+        let positional = parse_mapping(
+            "030000007e0500000920000000000000,Pro,a:b0,b:b1,x:b2,y:b3,face:bayx,platform:Linux",
+        )
+        .unwrap();
+        let face = positional.face_style(switch);
+        assert_eq!(face, FaceStyle::Bayx);
+        assert_eq!(
+            [A, B, X, Y].map(|button| switch.label(face, button)),
+            ["B", "A", "Y", "X"]
+        );
+
+        // Over the labels of the family
+        let sony = parse_mapping("03000000de2800000512000000000000,Pad,a:b0,face:sony,").unwrap();
+        let face = sony.face_style(ControllerType::Generic);
+        assert_eq!(ControllerType::Generic.label(face, X), "Square");
+        assert_eq!(ControllerType::Generic.glyph(face, A), "generic_cross");
+
+        // Entries for the labels of the hint name the buttons by them
+        let labeled = parse_mapping(
+            "030000007e0500000920000000000000,Pro,a:b1,b:b0,hint:SDL_GAMECONTROLLER_USE_BUTTON_LABELS:=1,platform:Linux",
+        )
+        .unwrap();
+        let face = labeled.face_style(switch);
+        assert_eq!(face, FaceStyle::Abxy);
+        assert_eq!(switch.label(face, A), "A");
+        assert_eq!(switch.glyph(face, B), "switch_b");
+
+        let positional = Mapping {
+            hint: Some(HintCondition::from_sdl_value(
+                "!SDL_GAMECONTROLLER_USE_BUTTON_LABELS:=1",
+            )),
+            ..labeled
+        };
+        assert_eq!(switch.label(positional.face_style(switch), A), "B");
+
+        // Unknown styles are kept as they are
+        let future = parse_mapping("03000000de2800000512000000000000,Pad,face:abcd,").unwrap();
+        assert_eq!(future.face, None);
+        assert_eq!(
+            future.to_string(),
+            "03000000de2800000512000000000000,Pad,face:abcd,"
+        );
+    }
+
+    #[test]
+    fn env_hints() {
+        let vars = [
+            ("SDL_TEST_ON", "1"),
+            ("SDL_TEST_OFF", "0"),
+            ("SDL_TEST_FALSE", "False"),
+            ("SDL_TEST_EMPTY", ""),
+            ("TEST_NOT_SDL", "1"),
+        ];
+        let hints = parse_hints(
+            vars.iter()
+                .map(|(name, value)| (OsString::from(name), OsString::from(value))),
+        );
+        let mut mappings = Mappings::with_hints(Platform::Linux, hints);
+
+        assert!(mappings.hint("SDL_TEST_ON", false));
+        assert!(!mappings.hint("SDL_TEST_OFF", true));
+        assert!(!mappings.hint("SDL_TEST_FALSE", true));
+        assert!(mappings.hint("SDL_TEST_EMPTY", true));
+        assert!(!mappings.hint("TEST_NOT_SDL", false));
+        assert!(!mappings.hint("SDL_TEST_UNSET", false));
+
+        // `set_hint` goes over the environment
+        mappings.set_hint("SDL_TEST_ON", false);
+        assert!(!mappings.hint("SDL_TEST_ON", true));
+    }
+
+    #[test]
+    #[cfg(feature = "mapping-db")]
+    fn database_layer() {
+        let (guid, lines) = database::entries().next().unwrap();
+        let (line, text) = lines[0];
+        let mappings = Mappings::new(Platform::current());
+        let origin = mappings.get(guid).unwrap().1;
+        assert_eq!(origin.layer, MappingLayer::Database);
+        assert_eq!(
+            MAPPINGS
+                .lines()
+                .nth(line - 1)
+                .map(|l| l.contains(&text[33..])),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn layers() {
+        let guid: Guid = "05000000123400005678000001000000".parse().unwrap();
+        let mut mappings = Mappings::new(Platform::current());
+        assert!(mappings.get(guid).is_none());
+
+        let platform = Platform::current().to_sdl_name();
+        let entry = format!("{},Game,a:b0,platform:{}", guid, platform);
+        let path =
+            std::env::temp_dir().join(format!("quad-gamepad-layers-{}.txt", std::process::id()));
+        std::fs::write(&path, format!("# game\n{}\n", entry)).unwrap();
+        mappings
+            .add_mappings_from_file(MappingLayer::Game, &path)
+            .unwrap();
+        let origin = mappings.get(guid).unwrap().1;
+        assert_eq!(origin.layer, MappingLayer::Game);
+        assert_eq!(origin.path.as_deref(), Some(path.as_path()));
+        assert_eq!(origin.line, Some(2));
+
+        mappings
+            .add_mapping(&entry.replace("Game", "Runtime"))
+            .unwrap();
+        // The user file comes later but is a lower layer
+        mappings
+            .add_mappings_from_file(MappingLayer::User, &path)
+            .unwrap();
+        let (mapping, origin) = mappings.get(guid).unwrap();
+        assert_eq!(mapping.name, "Runtime");
+        assert_eq!(origin, MappingOrigin::new(MappingLayer::Runtime));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn apply_bindings() {
+        use GamepadAxis::*;
+        use GamepadButton::*;
+
+        let mapping = parse_mapping(
+            "03000000de2800000512000001000000,Pad,a:b0,dpup:h0.1,dpleft:h0.8,leftx:a0~,+lefty:+a1,-lefty:-a1,x:-a2,lefttrigger:+a3,righttrigger:-a3,",
+        )
+        .unwrap();
+        let mut state = ControllerState::new();
+        let mut matches = AxisMatches::default();
+
+        mapping.set_button(&mut state, 0, true);
+        assert!(state.is_down(A));
+        mapping.set_hat(&mut state, 0, HAT_UP | HAT_LEFT);
+        assert!(state.is_down(DpadUp) && state.is_down(DpadLeft));
+        mapping.set_hat(&mut state, 0, HAT_LEFT);
+        assert!(!state.is_down(DpadUp) && state.is_down(DpadLeft));
+
+        mapping.set_axis(&mut state, &mut matches, 0, 0.5);
+        assert_eq!(state.axis(LeftX), -0.5);
+
+        mapping.set_axis(&mut state, &mut matches, 1, -0.5);
+        assert_eq!(state.axis(LeftY), -0.5);
+        mapping.set_axis(&mut state, &mut matches, 1, 0.25);
+        assert_eq!(state.axis(LeftY), 0.25);
+
+        // Half an axis to a button, released when the axis leaves that half
+        mapping.set_axis(&mut state, &mut matches, 2, -0.75);
+        assert!(state.is_down(X));
+        mapping.set_axis(&mut state, &mut matches, 2, 0.5);
+        assert!(!state.is_down(X));
+
+        // One axis for both triggers, the first one does not stay pressed
+        mapping.set_axis(&mut state, &mut matches, 3, 1.0);
+        assert_eq!(state.axis(LeftTrigger), 1.0);
+        assert!(state.is_down(TriggerLeft));
+        mapping.set_axis(&mut state, &mut matches, 3, -0.5);
+        assert_eq!(state.axis(LeftTrigger), 0.0);
+        assert!(!state.is_down(TriggerLeft));
+        assert_eq!(state.axis(RightTrigger), 0.5);
+        mapping.set_axis(&mut state, &mut matches, 3, 0.0);
+        assert_eq!(state.axis(RightTrigger), 0.0);
+    }
+
+    #[test]
+    fn switch_mapping() {
+        use GamepadAxis::*;
+        use GamepadButton::*;
+
+        let guid = "03000000de2800000512000001000000";
+        let old = parse_mapping(&format!("{},Pad,a:b0,leftx:a0,lefttrigger:a1,", guid)).unwrap();
+        let new = parse_mapping(&format!("{},Pad,b:b0,lefty:a0,", guid)).unwrap();
+        let raw = RawInput {
+            buttons: vec![true],
+            axes: vec![0.5, 1.0],
+            hats: vec![],
+        };
+        let mut state = ControllerState::new();
+        let mut matches = AxisMatches::default();
+        old.apply_raw(&raw, &mut state, &mut matches);
+        assert!(state.is_down(A) && state.is_down(TriggerLeft));
+        assert_eq!(state.axis(LeftX), 0.5);
+
+        state.begin_update();
+        new.apply_raw(&raw, &mut state, &mut matches);
+        assert!(state.was_released(A) && !state.is_down(A));
+        assert!(state.was_released(TriggerLeft));
+        assert!(state.was_pressed(B));
+        assert_eq!(state.axis(LeftX), 0.0);
+        assert_eq!(state.axis(LeftY), 0.5);
+        assert_eq!(state.axis(LeftTrigger), 0.0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |line: &str| parse_mapping(line).unwrap_err();
+        let guid = "05000000123400005678000001000000";
+        assert_eq!(
+            error("0500000012340000567800000100000g,Pad,a:b0"),
+            ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::InvalidGuid("0500000012340000567800000100000g".to_owned()),
+            }
+        );
+        assert_eq!(
+            error(&format!("{},Pad,a:b0,b1", guid)),
+            ParseError {
+                line: 1,
+                column: 43,
+                kind: ParseErrorKind::MissingColon,
+            }
+        );
+        assert_eq!(
+            error(&format!("{},Pad,a:b0,b:c1", guid)),
+            ParseError {
+                line: 1,
+                column: 45,
+                kind: ParseErrorKind::InvalidSource("c1".to_owned()),
+            }
+        );
+        assert_eq!(
+            error(&format!("{},Pad,+a:b0", guid)),
+            ParseError {
+                line: 1,
+                column: 38,
+                kind: ParseErrorKind::InvalidTarget("+a".to_owned()),
+            }
+        );
+        assert_eq!(
+            error(&format!("{},Pad,a:b0,platform:Amiga", guid)),
+            ParseError {
+                line: 1,
+                column: 52,
+                kind: ParseErrorKind::UnknownPlatform("Amiga".to_owned()),
+            }
+        );
+
+        // Lines are counted in files, comments and blank lines included
+        let (mappings, errors) = parse_mappings(&format!(
+            "# pads\n\n{0},Pad,a:b0,\n{0},Pad,a:b0,platform:Amiga\n",
+            guid
+        ));
+        assert_eq!(mappings.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (4, 52));
+    }
+
+    #[test]
+    fn file_entries_need_a_platform() {
+        let mut mappings = Mappings::new(Platform::Linux);
+        let without: Guid = "05000000123400005678000001000000".parse().unwrap();
+        let with: Guid = "05000000123400005778000001000000".parse().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("quad-gamepad-platform-{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            format!(
+                "{},No platform,a:b0,\n{},Linux,a:b0,platform:Linux,\n",
+                without, with
+            ),
+        )
+        .unwrap();
+        let errors = mappings
+            .add_mappings_from_file(MappingLayer::Game, &path)
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(errors, vec![]);
+        assert!(mappings.get(without).is_none());
+        assert_eq!(mappings.get(with).unwrap().0.name, "Linux");
     }
-    mappings_map
 }
@@ -0,0 +1,50 @@
+//! The bundled SDL mapping database. build.rs keeps the mappings for the
+//! target platform and lays them out in perfect hash tables keyed by GUID
+//! bytes, one by GUID and one by GUID without version. An entry is the lines
+//! for one GUID: an unconditional mapping and the conditional ones that came
+//! after it, in file order, with their line numbers in src/mappings.txt.
+
+use super::hash::hash;
+use crate::Guid;
+
+/// The lines of an entry, with their line numbers
+pub type Lines = &'static [(usize, &'static str)];
+
+include!(concat!(env!("OUT_DIR"), "/database.rs"));
+
+/// Slot of `guid` in a table, if it is there
+fn slot<T>(displacements: &[u32], entries: &[([u8; 16], T)], guid: Guid) -> Option<usize> {
+    if entries.is_empty() {
+        return None;
+    }
+
+    let key = guid.as_bytes();
+    let seed = displacements[(hash(0, key) % displacements.len() as u64) as usize];
+    let slot = (hash(seed, key) % entries.len() as u64) as usize;
+    Some(slot).filter(|slot| entries[*slot].0 == *key)
+}
+
+/// The database entry for `guid`
+pub fn lookup(guid: Guid) -> Option<Lines> {
+    slot(&DISPLACEMENTS, &ENTRIES, guid).map(|slot| ENTRIES[slot].1)
+}
+
+/// The database entries for any version of `guid` with the same CRC, in GUID
+/// order
+pub fn lookup_any_version(guid: Guid) -> impl Iterator<Item = Lines> {
+    let slots = slot(
+        &ANY_VERSION_DISPLACEMENTS,
+        &ANY_VERSION_ENTRIES,
+        guid.without_version(),
+    )
+    .map_or(&[][..], |slot| ANY_VERSION_ENTRIES[slot].1);
+    slots.iter().map(|slot| ENTRIES[*slot].1)
+}
+
+/// Every entry of the database with its GUID, in no particular order
+#[cfg(all(test, feature = "mapping-db"))]
+pub fn entries() -> impl Iterator<Item = (Guid, Lines)> {
+    ENTRIES
+        .iter()
+        .map(|(guid, lines)| (Guid::from_bytes(*guid), *lines))
+}
@@ -0,0 +1,17 @@
+// Shared with build.rs, which builds the database table with it
+
+/// FNV-1a of `key`, started from `seed` and finished with the splitmix64
+/// mixer so that every seed spreads keys differently
+pub fn hash(seed: u32, key: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ (seed as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for byte in key {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
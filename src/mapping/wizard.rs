@@ -0,0 +1,351 @@
+//! Step by step creation of a mapping for a controller, like SDL's
+//! controllermap.
+//!
+//! Prompt the player for `MappingSession::target`, feed
+//! `ControllerContext::raw_input` to `MappingSession::update` every frame and
+//! move on when it returns the input it captured. Full axes are captured by
+//! pushing them to their positive end: right for `leftx` and `rightx`, down
+//! for `lefty` and `righty`. Once done, `MappingSession::mapping().to_string()`
+//! can be saved to a mappings file or given to `ControllerContext::add_mapping`.
+
+use super::{AxisRange, Binding, Mapping, Platform, Source, Target};
+use super::{HAT_DOWN, HAT_LEFT, HAT_RIGHT, HAT_UP};
+use crate::{ControllerInfo, GamepadAxis, GamepadButton, Guid};
+
+/// Inputs of a device before any mapping, numbered the way mappings number
+/// them. Axes are in -1.0..1.0 and hats are `HAT_*` bits.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawInput {
+    pub buttons: Vec<bool>,
+    pub axes: Vec<f32>,
+    pub hats: Vec<u8>,
+}
+
+/// How far from where it rests an axis has to move to be captured
+const AXIS_CAPTURE_DISTANCE: f32 = 0.5;
+
+/// How close to where it rests an axis has to be to count as released
+const AXIS_RELEASE_DISTANCE: f32 = 0.25;
+
+/// Everything SDL mappings have names for, in the order SDL's controllermap
+/// asks for them
+fn default_targets() -> Vec<Target> {
+    use GamepadButton::*;
+
+    let mut targets: Vec<Target> = [
+        A,
+        B,
+        X,
+        Y,
+        Back,
+        Guide,
+        Start,
+        ThumbLeft,
+        ThumbRight,
+        BumperLeft,
+        BumperRight,
+        DpadUp,
+        DpadDown,
+        DpadLeft,
+        DpadRight,
+    ]
+    .iter()
+    .map(|button| Target::Button(*button))
+    .collect();
+
+    for axis in GamepadAxis::ALL {
+        targets.push(Target::Axis {
+            axis,
+            range: AxisRange::Full,
+        });
+    }
+
+    for button in [Misc1, Paddle1, Paddle2, Paddle3, Paddle4, Touchpad] {
+        targets.push(Target::Button(button));
+    }
+    targets
+}
+
+pub struct MappingSession {
+    guid: Guid,
+    name: String,
+    targets: Vec<Target>,
+    /// What was captured for each step so far, `None` for skipped ones
+    captured: Vec<Option<Source>>,
+    /// Input of the device when nothing is held, taken on the first update
+    rest: Option<RawInput>,
+    /// Something is still held since the last capture
+    waiting_release: bool,
+}
+
+impl MappingSession {
+    /// Ask for every button and axis SDL mappings have a name for
+    pub fn new(info: &ControllerInfo) -> MappingSession {
+        MappingSession::with_targets(info, default_targets())
+    }
+
+    /// Ask for `targets`, in that order
+    pub fn with_targets(info: &ControllerInfo, targets: Vec<Target>) -> MappingSession {
+        MappingSession {
+            guid: info.guid,
+            name: info.name.clone(),
+            targets,
+            captured: vec![],
+            rest: None,
+            waiting_release: false,
+        }
+    }
+
+    /// What to ask the player for, `None` once done
+    pub fn target(&self) -> Option<Target> {
+        self.targets.get(self.captured.len()).copied()
+    }
+
+    /// Index of the current step, out of `step_count`
+    pub fn step(&self) -> usize {
+        self.captured.len()
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.target().is_none()
+    }
+
+    /// Leave the current target unmapped
+    pub fn skip(&mut self) {
+        if !self.is_done() {
+            self.captured.push(None);
+        }
+    }
+
+    /// Go back to the previous step and forget what it captured
+    pub fn undo(&mut self) {
+        self.captured.pop();
+        self.waiting_release = true;
+    }
+
+    /// Feed the current input of the device. Returns what got captured for
+    /// the current target, once a new input moved far enough.
+    ///
+    /// Nothing should be held on the first update, it is taken as the rest
+    /// position of every input. Inputs already captured are ignored, and
+    /// everything has to be released between two captures.
+    pub fn update(&mut self, input: &RawInput) -> Option<Source> {
+        let rest = self.rest.get_or_insert_with(|| input.clone());
+        let target = self.targets.get(self.captured.len()).copied()?;
+
+        if self.waiting_release {
+            self.waiting_release = !is_released(rest, input);
+            return None;
+        }
+
+        let source = moved_input(rest, input, target)
+            .into_iter()
+            .find(|source| !self.is_captured(*source))?;
+
+        self.captured.push(Some(source));
+        self.waiting_release = true;
+        Some(source)
+    }
+
+    fn is_captured(&self, source: Source) -> bool {
+        self.captured.iter().flatten().any(|captured| {
+            match (*captured, source) {
+                // The other half of an axis already mapped by halves is free
+                (
+                    Source::Axis {
+                        index: a,
+                        range: range_a,
+                        ..
+                    },
+                    Source::Axis {
+                        index: b,
+                        range: range_b,
+                        ..
+                    },
+                ) => {
+                    a == b
+                        && (range_a == AxisRange::Full
+                            || range_b == AxisRange::Full
+                            || range_a == range_b)
+                }
+                (captured, source) => captured == source,
+            }
+        })
+    }
+
+    /// The mapping made of the steps done so far
+    pub fn mapping(&self) -> Mapping {
+        let mut mapping = Mapping::new(self.guid);
+        mapping.name = self.name.clone();
+        mapping.platform = Some(Platform::current());
+        mapping.bindings = self
+            .targets
+            .iter()
+            .zip(&self.captured)
+            .filter_map(|(target, source)| {
+                Some(Binding {
+                    target: *target,
+                    source: (*source)?,
+                })
+            })
+            .collect();
+        mapping
+    }
+}
+
+fn is_released(rest: &RawInput, input: &RawInput) -> bool {
+    let buttons = input.buttons.iter().all(|pressed| !pressed);
+    let axes = input
+        .axes
+        .iter()
+        .zip(&rest.axes)
+        .all(|(value, rest)| (value - rest).abs() < AXIS_RELEASE_DISTANCE);
+    let hats = input.hats.iter().all(|bits| *bits == 0);
+    buttons && axes && hats
+}
+
+/// Every input that moved, as the source it would be for `target`
+fn moved_input(rest: &RawInput, input: &RawInput, target: Target) -> Vec<Source> {
+    let mut res = vec![];
+
+    for (index, pressed) in input.buttons.iter().enumerate() {
+        if *pressed {
+            res.push(Source::Button(index));
+        }
+    }
+
+    for (index, (value, rest)) in input.axes.iter().zip(&rest.axes).enumerate() {
+        if (value - rest).abs() < AXIS_CAPTURE_DISTANCE {
+            continue;
+        }
+
+        let rests_at_end = rest.abs() > 0.5;
+        let stick = matches!(
+            target,
+            Target::Axis { axis, range: AxisRange::Full } if !axis.is_trigger()
+        );
+        // Triggers often rest at one end of the axis and go all the way to
+        // the other, sticks rest in the middle and go both ways
+        let (range, inverted) = if rests_at_end {
+            (AxisRange::Full, *rest > 0.0)
+        } else if stick {
+            (AxisRange::Full, *value < 0.0)
+        } else if *value > 0.0 {
+            (AxisRange::Positive, false)
+        } else {
+            (AxisRange::Negative, false)
+        };
+        res.push(Source::Axis {
+            index,
+            range,
+            inverted,
+        });
+    }
+
+    for (index, bits) in input.hats.iter().enumerate() {
+        // Diagonals are ambiguous
+        if [HAT_UP, HAT_RIGHT, HAT_DOWN, HAT_LEFT].contains(bits) {
+            res.push(Source::Hat { index, mask: *bits });
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(buttons: [bool; 2], axes: [f32; 3], hat: u8) -> RawInput {
+        RawInput {
+            buttons: buttons.to_vec(),
+            axes: axes.to_vec(),
+            hats: vec![hat],
+        }
+    }
+
+    #[test]
+    fn session() {
+        let info = ControllerInfo {
+            name: "Pad".to_owned(),
+            guid: "030000005e0400008e02000014010000".parse().unwrap(),
+            ..ControllerInfo::new()
+        };
+        let targets = ["a", "leftx", "lefttrigger", "b", "dpup"]
+            .iter()
+            .map(|name| Target::from_sdl_name(name).unwrap())
+            .collect();
+        let mut session = MappingSession::with_targets(&info, targets);
+
+        // The trigger on axis 2 rests at -1.0
+        let rest = input([false; 2], [0.0, 0.0, -1.0], 0);
+        assert_eq!(session.update(&rest), None);
+        assert_eq!(
+            session.update(&input([false; 2], [0.3, 0.0, -1.0], 0)),
+            None
+        );
+
+        let held = input([false, true], [0.0, 0.0, -1.0], 0);
+        assert_eq!(session.update(&held), Some(Source::Button(1)));
+        // Still held for the next step
+        assert_eq!(session.update(&held), None);
+        assert_eq!(session.update(&rest), None);
+
+        let axis = |index, range, inverted| Source::Axis {
+            index,
+            range,
+            inverted,
+        };
+        let pushed = input([false; 2], [0.9, 0.0, -1.0], 0);
+        assert_eq!(
+            session.update(&pushed),
+            Some(axis(0, AxisRange::Full, false))
+        );
+        assert_eq!(session.update(&rest), None);
+
+        let pulled = input([false; 2], [0.0, 0.0, 1.0], 0);
+        assert_eq!(
+            session.update(&pulled),
+            Some(axis(2, AxisRange::Full, false))
+        );
+        assert_eq!(session.update(&rest), None);
+
+        session.skip();
+        let up = input([false; 2], [0.0, 0.0, -1.0], HAT_UP);
+        assert_eq!(
+            session.update(&up),
+            Some(Source::Hat {
+                index: 0,
+                mask: HAT_UP
+            })
+        );
+        assert!(session.is_done());
+
+        // Back to the d-pad, the button of `a` is taken
+        session.undo();
+        assert_eq!(session.target(), Target::from_sdl_name("dpup"));
+        assert_eq!(session.update(&up), None);
+        assert_eq!(session.update(&rest), None);
+        assert_eq!(session.update(&held), None);
+        assert_eq!(session.update(&rest), None);
+        assert_eq!(
+            session.update(&up),
+            Some(Source::Hat {
+                index: 0,
+                mask: HAT_UP
+            })
+        );
+
+        assert_eq!(
+            session.mapping().to_string(),
+            format!(
+                "030000005e0400008e02000014010000,Pad,a:b1,dpup:h0.1,lefttrigger:a2,leftx:a0,platform:{},",
+                Platform::current().to_sdl_name()
+            )
+        );
+    }
+}
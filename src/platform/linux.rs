@@ -1,115 +1,499 @@
 // https://github.com/glfw/glfw/blob/master/src/linux_joystick.c
 
-use crate::{ControllerInfo, ControllerState, ControllerStatus, DEFAULT_CONTROLLER_STATE};
+use crate::{BusType, ControllerInfo, ControllerState, ControllerStatus, ControllerType, Guid};
 
-use std::path::PathBuf;
+use crate::diagnostic::report;
+use crate::mapping::{
+    AxisMatches, Mapping, MappingLayer, MappingOrigin, Mappings, HAT_DOWN, HAT_LEFT, HAT_RIGHT,
+    HAT_UP,
+};
+use crate::{Backend, Diagnostic, Error, RawInput};
 
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+mod auto_mapping;
 mod ioctl;
 mod linux_input;
+mod steam_deck;
 
 use self::ioctl::{InputAbsInfo, InputEvent, InputId};
 use self::linux_input::*;
+pub use self::steam_deck::SteamDeckBackend;
 
 fn is_bit_set(bit: usize, arr: &[u8]) -> bool {
-    return (arr[bit / 8] & (1 << ((bit as usize) % 8))) != 0;
+    (arr[bit / 8] & (1 << (bit % 8))) != 0
+}
+
+/// Key codes in the order buttons are numbered, the same as SDL so that
+/// mappings from the database line up: joystick and gamepad buttons first,
+/// then the keys and buttons below them
+fn button_codes() -> impl Iterator<Item = libc::c_int> {
+    (BTN_JOYSTICK..KEY_CNT).chain(0..BTN_JOYSTICK)
+}
+
+fn bus_type(bustype: u16) -> BusType {
+    match bustype as _ {
+        BUS_USB => BusType::Usb,
+        BUS_BLUETOOTH => BusType::Bluetooth,
+        BUS_VIRTUAL => BusType::Virtual,
+        _ => BusType::Other(bustype),
+    }
+}
+
+/// The string a string ioctl filled `bytes` with, up to its NUL or to the
+/// end of `bytes` when it was too long to get one
+fn string_bytes(bytes: &[u8]) -> &[u8] {
+    let len = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+// Generate a joystick GUID that matches the SDL 2.26+ one, name CRC included.
+// Like SDL, vendor and product ids are enough, the version may be 0.
+fn create_guid(id: &InputId, name_bytes: &[u8; 256]) -> Guid {
+    let name = string_bytes(name_bytes);
+    let guid = if id.vendor != 0 && id.product != 0 {
+        Guid::from_ids(id.bustype, id.vendor, id.product, id.version)
+    } else {
+        Guid::from_name(id.bustype, name)
+    };
+    guid.with_name_crc(name)
+}
+
+/// After a failed read of `path`, whether the device is gone. Failures
+/// other than the device being unplugged are reported.
+fn device_lost(path: &Path) -> bool {
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EAGAIN) | Some(libc::EINTR) => false,
+        Some(libc::ENODEV) => true,
+        _ => {
+            report(Diagnostic::ReadError {
+                path: path.display().to_string(),
+                error,
+            });
+            true
+        }
+    }
 }
 
+fn reject(path: &Path, reason: &'static str) {
+    report(Diagnostic::DeviceRejected {
+        path: path.display().to_string(),
+        reason,
+    });
+}
+
+/// Reads one of the string ioctls, EVIOCGPHYS or HIDIOCGRAWUNIQ for example
+fn read_string(fd: libc::c_int, request: fn(u32) -> u64) -> Option<String> {
+    let mut bytes: [u8; 256] = [0; 256];
+    if unsafe { libc::ioctl(fd, request(256), bytes.as_mut_ptr()) } < 0 {
+        return None;
+    }
+    let string = String::from_utf8_lossy(string_bytes(&bytes));
+    if string.is_empty() {
+        None
+    } else {
+        Some(string.into_owned())
+    }
+}
+
+/// A physical controller, made of one or more evdev nodes
 struct GamePad {
-    fd: libc::c_int,
     info: ControllerInfo,
     state: ControllerState,
-    axis_map: [i32; ABS_CNT as usize],
-    axis_info: [InputAbsInfo; ABS_CNT as usize],
-    buttons_map: [usize; (KEY_CNT - BTN_MISC) as usize],
-    mapping: crate::mapping::Mapping,
+    nodes: Vec<Node>,
+    mapping: Mapping,
+    axis_matches: AxisMatches,
+    /// Buttons, axes and hats of the device, before the mapping
+    raw: RawInput,
+    /// `Probe::group` of the nodes
+    group: String,
 }
 
 impl GamePad {
-    unsafe fn poll_abs_info(&mut self) {
-        for code in &self.axis_map {
-            if *code != -1 {
-                if libc::ioctl(
-                    self.fd,
-                    ioctl::eviocgabs(*code as _),
-                    &mut self.axis_info[*code as usize],
+    fn set_mapping(&mut self, mapping: Mapping, origin: MappingOrigin) {
+        self.info.face_style = mapping.face_style(self.info.controller_type);
+        self.mapping = mapping;
+        self.info.mapping = origin;
+        self.count_inputs();
+        self.mapping
+            .apply_raw(&self.raw, &mut self.state, &mut self.axis_matches);
+    }
+
+    /// The buttons and axes the mapping gives the device
+    fn count_inputs(&mut self) {
+        let (buttons, axes, hats) = (
+            self.raw.buttons.len(),
+            self.raw.axes.len(),
+            self.raw.hats.len(),
+        );
+        self.info.buttons = self.mapping.buttons(buttons, axes, hats);
+        self.info.analog_count = self.mapping.analog_count(buttons, axes, hats);
+    }
+
+    /// Read `probe` as part of the device too. Its buttons, axes and hats
+    /// are numbered after the ones of the nodes before it.
+    unsafe fn add_node(&mut self, probe: Probe) {
+        let mut kind = probe.kind();
+        match kind {
+            NodeKind::Motion => self.info.has_motion = true,
+            NodeKind::Touchpad(_) if self.info.touchpad_count < crate::MAX_TOUCHPADS => {
+                kind = NodeKind::Touchpad(self.info.touchpad_count);
+                self.info.touchpad_count += 1;
+            }
+            NodeKind::Touchpad(_) => {
+                libc::close(probe.fd);
+                reject(&probe.path, "more touchpads than MAX_TOUCHPADS");
+                return;
+            }
+            NodeKind::Gamepad => {}
+        }
+
+        let mut node = Node {
+            path: probe.path,
+            fd: probe.fd,
+            kind,
+            axis_map: [-1; ABS_CNT as usize],
+            axis_info: [InputAbsInfo::default(); ABS_CNT as usize],
+            buttons_map: [usize::MAX; KEY_CNT as usize],
+            hat_map: [-1; HAT_CNT],
+            hat_values: [[0; 2]; HAT_CNT],
+        };
+
+        // Like SDL, hats are numbered on their own and are not axes
+        if kind == NodeKind::Gamepad {
+            for hat in 0..HAT_CNT {
+                let code = ABS_HAT0X as usize + hat * 2;
+                if is_bit_set(code, &probe.abs_bits) || is_bit_set(code + 1, &probe.abs_bits) {
+                    node.hat_map[hat] = self.raw.hats.len() as i32;
+                    self.raw.hats.push(0);
+                }
+            }
+        }
+
+        for code in 0..ABS_CNT {
+            if !is_bit_set(code as _, &probe.abs_bits) {
+                continue;
+            }
+
+            if (ABS_HAT0X..=ABS_HAT3Y).contains(&code)
+                || libc::ioctl(
+                    node.fd,
+                    ioctl::eviocgabs(code as _),
+                    &mut node.axis_info[code as usize],
                 ) < 0
-                {
+            {
+                continue;
+            }
+            if kind == NodeKind::Gamepad {
+                node.axis_map[code as usize] = self.raw.axes.len() as i32;
+                // Axes do not all rest at 0, triggers often rest at -1
+                self.raw
+                    .axes
+                    .push(node.normalized(code as u16, node.axis_info[code as usize].value));
+            }
+        }
+
+        if kind == NodeKind::Gamepad {
+            for code in button_codes() {
+                if !is_bit_set(code as _, &probe.key_bits) {
                     continue;
                 }
+
+                node.buttons_map[code as usize] = self.raw.buttons.len();
+                self.raw.buttons.push(false);
             }
+            node.poll_abs_info();
+            self.count_inputs();
         }
+
+        self.nodes.push(node);
     }
 
     unsafe fn poll(&mut self) {
+        for node in &mut self.nodes {
+            if !node.poll(
+                &mut self.state,
+                &mut self.raw,
+                &self.mapping,
+                &mut self.axis_matches,
+            ) {
+                self.state.status = ControllerStatus::Disconnected;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NodeKind {
+    /// Buttons and axes, numbered after the ones of the previous nodes
+    Gamepad,
+    /// Accelerometer and gyroscope, INPUT_PROP_ACCELEROMETER
+    Motion,
+    /// Touchpad, reported in `touchpads[index]`
+    Touchpad(usize),
+}
+
+const HAT_CNT: usize = ((ABS_HAT3Y - ABS_HAT0X) / 2 + 1) as usize;
+
+/// One /dev/input/event* node of a controller
+struct Node {
+    path: PathBuf,
+    fd: libc::c_int,
+    kind: NodeKind,
+    axis_map: [i32; ABS_CNT as usize],
+    axis_info: [InputAbsInfo; ABS_CNT as usize],
+    /// Index of each key code among the buttons, `usize::MAX` for codes the
+    /// node does not report
+    buttons_map: [usize; KEY_CNT as usize],
+    /// Index of ABS_HAT0X/ABS_HAT0Y.. among the hats of the controller
+    hat_map: [i32; HAT_CNT],
+    /// Last ABS_HATnX and ABS_HATnY values
+    hat_values: [[i32; 2]; HAT_CNT],
+}
+
+impl Node {
+    unsafe fn poll_abs_info(&mut self) {
+        for code in 0..ABS_CNT as usize {
+            if self.axis_map[code] != -1 {
+                libc::ioctl(
+                    self.fd,
+                    ioctl::eviocgabs(code as _),
+                    &mut self.axis_info[code],
+                );
+            }
+        }
+    }
+
+    /// False once the node is gone, when the controller was unplugged
+    unsafe fn poll(
+        &mut self,
+        state: &mut ControllerState,
+        raw: &mut RawInput,
+        mapping: &Mapping,
+        matches: &mut AxisMatches,
+    ) -> bool {
         let mut e = InputEvent::default();
 
-        if libc::read(
-            self.fd,
-            &mut e as *mut _ as *mut _,
-            std::mem::size_of_val(&e),
-        ) < 0
-        {
-            // handle disconnect
+        loop {
+            let len = libc::read(
+                self.fd,
+                &mut e as *mut _ as *mut _,
+                std::mem::size_of_val(&e),
+            );
+            if len != std::mem::size_of_val(&e) as isize {
+                return !(len < 0 && device_lost(&self.path));
+            }
+
+            match self.kind {
+                NodeKind::Gamepad => self.gamepad_event(&e, state, raw, mapping, matches),
+                NodeKind::Motion => self.motion_event(&e, state),
+                NodeKind::Touchpad(index) => self.touchpad_event(&e, state, index),
+            }
+        }
+    }
+
+    fn normalized(&self, code: u16, value: i32) -> f32 {
+        let info = self.axis_info[code as usize];
+        ((value as f32 - info.minimum as f32) / (info.maximum as f32 - info.minimum as f32) - 0.5)
+            * 2.
+    }
+
+    fn gamepad_event(
+        &mut self,
+        e: &InputEvent,
+        state: &mut ControllerState,
+        raw: &mut RawInput,
+        mapping: &Mapping,
+        matches: &mut AxisMatches,
+    ) {
+        if e.type_ == EV_KEY as _ {
+            let index = match self.buttons_map.get(e.code as usize) {
+                Some(index) if *index != usize::MAX => *index,
+                _ => return,
+            };
+            raw.buttons[index] = e.value != 0;
+            mapping.set_button(state, index, e.value != 0);
+        }
+        if e.type_ == EV_ABS as _ && (ABS_HAT0X as u16..=ABS_HAT3Y as u16).contains(&e.code) {
+            let hat = (e.code - ABS_HAT0X as u16) as usize / 2;
+            if self.hat_map[hat] == -1 {
+                return;
+            }
+            self.hat_values[hat][(e.code - ABS_HAT0X as u16) as usize % 2] = e.value;
+
+            let [x, y] = self.hat_values[hat];
+            let mut bits = 0;
+            if y < 0 {
+                bits |= HAT_UP;
+            }
+            if x > 0 {
+                bits |= HAT_RIGHT;
+            }
+            if y > 0 {
+                bits |= HAT_DOWN;
+            }
+            if x < 0 {
+                bits |= HAT_LEFT;
+            }
+            raw.hats[self.hat_map[hat] as usize] = bits;
+            mapping.set_hat(state, self.hat_map[hat] as usize, bits);
+        } else if e.type_ == EV_ABS as _ && self.axis_map[e.code as usize] != -1 {
+            let value = self.normalized(e.code, e.value);
+            let index = self.axis_map[e.code as usize] as usize;
+            raw.axes[index] = value;
+            mapping.set_axis(state, matches, index, value);
+        }
+    }
+
+    fn motion_event(&self, e: &InputEvent, state: &mut ControllerState) {
+        if e.type_ != EV_ABS as _ || e.code > ABS_RZ as _ {
             return;
         }
 
+        // Resolution is units per g for ABS_X..ABS_Z and units per degree
+        // per second for ABS_RX..ABS_RZ
+        let resolution = self.axis_info[e.code as usize].resolution.max(1) as f32;
+        let value = e.value as f32 / resolution;
+        match e.code as usize {
+            code @ 0..=2 => state.motion.accel[code] = value,
+            code => state.motion.gyro[code - 3] = value,
+        }
+    }
+
+    fn touchpad_event(&self, e: &InputEvent, state: &mut ControllerState, index: usize) {
+        let touchpad = &mut state.touchpads[index];
+
         if e.type_ == EV_KEY as _ {
-            let code = e.code as usize - BTN_MISC as usize;
-            self.state.digital_state[self.mapping.buttons[self.buttons_map[code]] as usize] =
-                e.value != 0;
+            match e.code as _ {
+                BTN_TOUCH => touchpad.touched = e.value != 0,
+                BTN_LEFT => touchpad.pressed = e.value != 0,
+                _ => {}
+            }
         }
         if e.type_ == EV_ABS as _ {
-            let info = self.axis_info[e.code as usize];
-            let value = if e.code >= ABS_HAT0X as _ && e.code <= ABS_HAT3Y as _ {
-                e.value as f32
-            } else {
-                ((e.value as f32 - info.minimum as f32)
-                    / (info.maximum as f32 - info.minimum as f32)
-                    - 0.5)
-                    * 2.
-            };
-            self.state.analog_state[self.axis_map[e.code as usize] as usize] = value;
+            match e.code as _ {
+                ABS_X => touchpad.x = self.normalized(e.code, e.value),
+                ABS_Y => touchpad.y = self.normalized(e.code, e.value),
+                ABS_PRESSURE => touchpad.pressure = self.normalized(e.code, e.value) * 0.5 + 0.5,
+                _ => {}
+            }
         }
     }
 }
 
-unsafe fn open_joystick_device(
-    mappings: &crate::mapping::MappingsMap,
+impl Drop for Node {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Everything read from an evdev node before deciding what it belongs to
+struct Probe {
     path: PathBuf,
-) -> Option<GamePad> {
+    fd: libc::c_int,
+    id: InputId,
+    name: String,
+    name_bytes: [u8; 256],
+    key_bits: [u8; (KEY_CNT as usize).div_ceil(8)],
+    abs_bits: [u8; (ABS_CNT as usize).div_ceil(8)],
+    prop_bits: [u8; (INPUT_PROP_CNT as usize).div_ceil(8)],
+    phys: Option<String>,
+    uniq: Option<String>,
+    group: String,
+}
+
+impl Probe {
+    fn kind(&self) -> NodeKind {
+        if is_bit_set(INPUT_PROP_ACCELEROMETER as _, &self.prop_bits) {
+            NodeKind::Motion
+        } else if is_bit_set(INPUT_PROP_POINTER as _, &self.prop_bits)
+            || is_bit_set(INPUT_PROP_BUTTONPAD as _, &self.prop_bits)
+        {
+            NodeKind::Touchpad(0)
+        } else {
+            NodeKind::Gamepad
+        }
+    }
+
+    fn buttons_count(&self) -> usize {
+        button_codes()
+            .filter(|code| is_bit_set(*code as _, &self.key_bits))
+            .count()
+    }
+}
+
+// Nodes of one controller share the parent device of their input device in
+// sysfs, the HID device for hid drivers or the USB interface for xpad.
+// Without sysfs, fall back to the physical path and unique id.
+fn group_key(path: &Path, phys: Option<&str>, uniq: Option<&str>) -> String {
+    if let Some(file_name) = path.file_name() {
+        let parent = Path::new("/sys/class/input")
+            .join(file_name)
+            .join("device/device");
+        if let Ok(parent) = std::fs::canonicalize(parent) {
+            return parent.to_string_lossy().into_owned();
+        }
+    }
+
+    if let Some(phys) = phys {
+        // "usb-0000:00:14.0-2/input0" and "usb-0000:00:14.0-2/input1" are
+        // interfaces of the same controller
+        let phys = phys
+            .rsplit_once("/input")
+            .map_or(phys, |(prefix, _)| prefix);
+        return format!("{}/{}", phys, uniq.unwrap_or_default());
+    }
+
+    path.to_string_lossy().into_owned()
+}
+
+unsafe fn probe_device(path: PathBuf) -> Option<Probe> {
     use std::os::unix::ffi::OsStrExt;
 
     let filename = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
     let fd = libc::open(filename.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK);
     if fd == -1 {
+        reject(&path, "cannot be opened");
         return None;
     }
 
-    let mut ev_bits: [u8; (EV_CNT as usize + 7) / 8] = [0; (EV_CNT as usize + 7) / 8];
-    let mut key_bits: [u8; (KEY_CNT as usize + 7) / 8] = [0; (KEY_CNT as usize + 7) / 8];
-    let mut abs_bits: [u8; (ABS_CNT as usize + 7) / 8] = [0; (ABS_CNT as usize + 7) / 8];
+    let mut ev_bits = [0u8; (EV_CNT as usize).div_ceil(8)];
+    let mut key_bits = [0u8; (KEY_CNT as usize).div_ceil(8)];
+    let mut abs_bits = [0u8; (ABS_CNT as usize).div_ceil(8)];
+    let mut prop_bits = [0u8; (INPUT_PROP_CNT as usize).div_ceil(8)];
 
     let eviocgbit: u64 = ioctl::eviocgbit(0, std::mem::size_of_val(&ev_bits) as _);
     let eviocgbit_ev_key: u64 =
         ioctl::eviocgbit(EV_KEY as _, std::mem::size_of_val(&key_bits) as _);
     let eviocgbit_ev_abs: u64 =
         ioctl::eviocgbit(EV_ABS as _, std::mem::size_of_val(&abs_bits) as _);
+    let eviocgprop: u64 = ioctl::eviocgprop(std::mem::size_of_val(&prop_bits) as _);
 
     let mut id: InputId = InputId::default();
 
     if libc::ioctl(fd, eviocgbit, ev_bits.as_mut_ptr()) < 0
         || libc::ioctl(fd, eviocgbit_ev_key, key_bits.as_mut_ptr()) < 0
         || libc::ioctl(fd, eviocgbit_ev_abs, abs_bits.as_mut_ptr()) < 0
+        || libc::ioctl(fd, eviocgprop, prop_bits.as_mut_ptr()) < 0
         || libc::ioctl(fd, ioctl::eviocgid(), &mut id as *mut _) < 0
     {
         libc::close(fd);
-        println!("ioctl failed, bad");
+        reject(&path, "not an evdev device");
         return None;
     }
 
-    // Ensure this device supports the events expected of a joystick
-    if !is_bit_set(EV_KEY as _, &ev_bits) || !is_bit_set(EV_ABS as _, &ev_bits) {
+    // Every node of a controller reports axes, motion sensors have no keys
+    if !is_bit_set(EV_ABS as _, &ev_bits) {
         libc::close(fd);
+        reject(&path, "no axes");
         return None;
     }
 
@@ -117,152 +501,342 @@ unsafe fn open_joystick_device(
     let mut name_bytes: [u8; 256] = [0; 256];
     let eviocgname: u64 = ioctl::eviocgname(256);
     let name = if libc::ioctl(fd, eviocgname, name_bytes.as_mut_ptr()) >= 0 {
-        std::ffi::CStr::from_ptr(name_bytes.as_ptr() as *const _)
-            .to_string_lossy()
-            .into_owned()
+        String::from_utf8_lossy(string_bytes(&name_bytes)).into_owned()
     } else {
         "Unknown".to_string()
     };
-    println!("Found gamepad {:?}: {:?}", path, name);
-    println!("input_id: {:?}", id);
-
-    // Generate a joystick GUID that matches the SDL 2.0.5+ one
-    #[rustfmt::skip]
-    let guid = if id.vendor != 0 && id.product != 0 && id.version != 0 {
-        format!(
-            "{:02x}{:02x}0000{:02x}{:02x}0000{:02x}{:02x}0000{:02x}{:02x}0000",
-            id.bustype & 0xff, id.bustype >> 8,
-            id.vendor & 0xff,  id.vendor >> 8,
-            id.product & 0xff, id.product >> 8,
-            id.version & 0xff, id.version >> 8,
-        )
-    } else {
-        format!(
-            "{:2x}{:2x}0000{:2x}{:2x}{:2x}{:2x}{:2x}{:2x}{:2x}{:2x}{:2x}{:2x}{:2x}00",
-            id.bustype & 0xff, id.bustype >> 8,
-            name_bytes[0], name_bytes[1], name_bytes[2], name_bytes[3],
-            name_bytes[4], name_bytes[5], name_bytes[6], name_bytes[7],
-            name_bytes[8], name_bytes[9], name_bytes[10])
-    };
-
-    let mapping = mappings.get(&guid).cloned().unwrap_or_else(|| {
-        println!("No mapping for {}, falling back to default!", guid);
-        crate::mapping::Mapping::new(&guid)
-    });
 
-    let mut digital_count = 0;
-    let mut buttons = vec![];
-    let mut buttons_map = [0; (KEY_CNT - BTN_MISC) as usize];
-    for code in BTN_MISC..KEY_CNT {
-        if !is_bit_set(code as _, &key_bits) {
-            continue;
-        }
+    let phys = read_string(fd, ioctl::eviocgphys);
+    let uniq = read_string(fd, ioctl::eviocguniq);
+    let group = group_key(&path, phys.as_deref(), uniq.as_deref());
 
-        buttons_map[(code - BTN_MISC) as usize] = digital_count;
-        buttons.push(mapping.buttons[digital_count]);
-        digital_count += 1;
+    // A node without keys can still be the pedals or the motion sensors of
+    // a controller, but never a controller on its own
+    if !is_bit_set(EV_KEY as _, &ev_bits) {
+        key_bits = [0; (KEY_CNT as usize).div_ceil(8)];
     }
 
-    let mut analog_count = 0;
-    let mut axis_map = [-1; ABS_CNT as usize];
-    let mut axis_info = [InputAbsInfo::default(); ABS_CNT as usize];
-
-    for code in 0..ABS_CNT {
-        if !is_bit_set(code as _, &abs_bits) {
-            continue;
-        }
+    Some(Probe {
+        path,
+        fd,
+        id,
+        name,
+        name_bytes,
+        key_bits,
+        abs_bits,
+        prop_bits,
+        phys,
+        uniq,
+        group,
+    })
+}
 
-        if code >= ABS_HAT0X && code <= ABS_HAT3Y {
-            axis_map[code as usize] = analog_count as i32;
-            analog_count += 1;
-        } else {
-            if libc::ioctl(
-                fd,
-                ioctl::eviocgabs(code as _),
-                &mut axis_info[code as usize],
-            ) < 0
-            {
-                continue;
+unsafe fn open_joystick_device(mappings: &Mappings, mut probes: Vec<Probe>) -> Option<GamePad> {
+    // The node with the most buttons is the controller itself, the others
+    // are its motion sensors, touchpad or pedals
+    let primary = probes
+        .iter()
+        .enumerate()
+        .filter(|(_, probe)| probe.kind() == NodeKind::Gamepad && probe.buttons_count() != 0)
+        .max_by_key(|(_, probe)| probe.buttons_count())
+        .map(|(i, _)| i);
+    let primary = match primary {
+        Some(primary) => primary,
+        None => {
+            for probe in probes {
+                libc::close(probe.fd);
+                reject(
+                    &probe.path,
+                    "no buttons on this node or the others of its device",
+                );
             }
-            axis_map[code as usize] = analog_count as i32;
-            analog_count += 1;
+            return None;
         }
-    }
+    };
+    let primary = probes.remove(primary);
+
+    let id = &primary.id;
+    let guid = create_guid(id, &primary.name_bytes);
+    let key_bits = primary.key_bits;
+    let mut abs_bits = primary.abs_bits;
 
     let mut gamepad = GamePad {
-        fd,
         info: ControllerInfo {
-            name,
-            buttons,
-            analog_count,
+            name: primary.name.clone(),
+            vendor_id: id.vendor,
+            product_id: id.product,
+            version: id.version,
+            bus: bus_type(id.bustype),
+            guid,
+            controller_type: ControllerType::from_ids(id.vendor, id.product),
+            serial: primary.uniq.clone(),
+            physical_path: primary.phys.clone(),
+            ..ControllerInfo::new()
         },
         state: ControllerState::new(),
-        axis_info,
-        axis_map,
-        buttons_map,
-        mapping,
+        nodes: vec![],
+        mapping: Mapping::new(guid),
+        axis_matches: AxisMatches::default(),
+        raw: RawInput::default(),
+        group: primary.group.clone(),
     };
+    gamepad.add_node(primary);
+    for probe in probes {
+        gamepad.add_node(probe);
+    }
+
+    // The axes of the primary node that cannot be read are not numbered
+    for code in 0..ABS_CNT as usize {
+        if !(ABS_HAT0X as usize..=ABS_HAT3Y as usize).contains(&code)
+            && gamepad.nodes[0].axis_map[code] == -1
+        {
+            abs_bits[code / 8] &= !(1 << (code % 8));
+        }
+    }
+    let (mapping, origin) = mappings.get(guid).unwrap_or_else(|| {
+        let name = &gamepad.info.name;
+        match auto_mapping::create_mapping(guid, name, gamepad.info.vendor_id, &key_bits, &abs_bits)
+        {
+            Some(mapping) => (mapping, MappingOrigin::new(MappingLayer::Automatic)),
+            None => (Mapping::new(guid), MappingOrigin::default()),
+        }
+    });
+    gamepad.set_mapping(mapping, origin);
     gamepad.state.status = ControllerStatus::Connected;
-    gamepad.poll_abs_info();
+
+    report(Diagnostic::DeviceFound {
+        name: gamepad.info.name.clone(),
+        guid,
+        path: Some(gamepad.nodes[0].path.display().to_string()),
+    });
+    report(Diagnostic::mapping(&gamepad.info));
 
     Some(gamepad)
 }
 
-unsafe fn platform_init_joysticks(mappings: &crate::mapping::MappingsMap) -> Vec<GamePad> {
-    let dirname = "/dev/input";
+/// How often backends look for devices that were plugged in
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The nodes of `dir` named `prefix` and a number, in the order of their
+/// numbers. Empty when `dir` is missing or cannot be read, in containers and
+/// sandboxes without input devices.
+fn device_nodes(dir: &str, prefix: &str) -> io::Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err)
+            if err.kind() == io::ErrorKind::NotFound
+                || err.kind() == io::ErrorKind::PermissionDenied =>
+        {
+            return Ok(vec![]);
+        }
+        Err(err) => return Err(err),
+    };
 
-    let mut res = vec![];
+    let mut paths = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if let Some(number) = entry
+            .file_name()
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(prefix))
+        {
+            paths.push((number.parse::<usize>().unwrap_or(usize::MAX), entry.path()));
+        }
+    }
+    paths.sort();
+    Ok(paths.into_iter().map(|(_, path)| path).collect())
+}
 
-    for entry in std::fs::read_dir(dirname).unwrap() {
-        let path = entry.unwrap().path();
-        let file_name = path.file_name().unwrap().to_str().unwrap();
+/// Device nodes of one kind that a backend opened or rejected
+struct Scanner {
+    dir: &'static str,
+    prefix: &'static str,
+    /// What the backend opens the nodes for, `libc::R_OK` and `libc::W_OK`
+    access: libc::c_int,
+    /// Nodes that come back after they were gone are scanned again
+    scanned: Vec<PathBuf>,
+    last_scan: Option<Instant>,
+}
+
+impl Scanner {
+    fn new(dir: &'static str, prefix: &'static str, access: libc::c_int) -> Scanner {
+        Scanner {
+            dir,
+            prefix,
+            access,
+            scanned: vec![],
+            last_scan: None,
+        }
+    }
 
-        if file_name.starts_with("event") {
-            if let Some(gamepad) = open_joystick_device(mappings, path) {
-                res.push(gamepad);
+    /// Nodes that showed up since the last scan, once per `SCAN_INTERVAL`.
+    /// Nodes without access yet are left for the next scan, udev sets the
+    /// permissions of new nodes a moment after creating them.
+    fn scan(&mut self) -> io::Result<Vec<PathBuf>> {
+        use std::os::unix::ffi::OsStrExt;
+
+        if self
+            .last_scan
+            .is_some_and(|last_scan| last_scan.elapsed() < SCAN_INTERVAL)
+        {
+            return Ok(vec![]);
+        }
+        self.last_scan = Some(Instant::now());
+
+        let paths = device_nodes(self.dir, self.prefix)?;
+        self.scanned.retain(|path| paths.contains(path));
+
+        let mut res = vec![];
+        for path in paths {
+            let filename = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+            if self.scanned.contains(&path)
+                || unsafe { libc::access(filename.as_ptr(), self.access) } != 0
+            {
+                continue;
             }
+            self.scanned.push(path.clone());
+            res.push(path);
+        }
+        Ok(res)
+    }
+
+    /// Scan the nodes of a closed device again, in case it comes back
+    /// before the next scan notices it was gone
+    fn forget<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) {
+        for path in paths {
+            self.scanned.retain(|scanned| scanned != path);
         }
     }
-    res
 }
 
-pub struct ControllerContext {
-    gamepads: Vec<GamePad>,
+/// Controllers read from their /dev/input/event* nodes
+pub struct EvdevBackend {
+    gamepads: Vec<(usize, GamePad)>,
+    next_key: usize,
+    scanner: Scanner,
 }
 
-impl ControllerContext {
-    pub fn new() -> Option<Self> {
-        let mappings = crate::mapping::read_mappings_file(crate::mapping::Platform::Linux);
-        Some(ControllerContext {
-            gamepads: unsafe { platform_init_joysticks(&mappings) },
-        })
+impl EvdevBackend {
+    pub fn new() -> EvdevBackend {
+        EvdevBackend {
+            gamepads: vec![],
+            next_key: 0,
+            scanner: Scanner::new("/dev/input", "event", libc::R_OK),
+        }
     }
 
-    /// Update controller state by index
-    pub fn update(&mut self) {
-        for index in 0..crate::MAX_DEVICES {
-            if let Some(ref mut gamepad) = self.gamepads.get_mut(index) {
-                gamepad.state.digital_state_prev = gamepad.state.digital_state;
+    fn gamepad(&self, device: usize) -> Option<&GamePad> {
+        self.gamepads
+            .iter()
+            .find(|(key, _)| *key == device)
+            .map(|(_, gamepad)| gamepad)
+    }
+}
 
-                unsafe {
-                    gamepad.poll();
-                }
+impl Default for EvdevBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for EvdevBackend {
+    fn enumerate(
+        &mut self,
+        mappings: &Mappings,
+        claimed: &dyn Fn(u16, u16) -> bool,
+    ) -> Result<Vec<usize>, Error> {
+        // Keep event0, event1.. order within and across controllers
+        let mut groups: Vec<Vec<Probe>> = vec![];
+        for path in self.scanner.scan()? {
+            let probe = match unsafe { probe_device(path) } {
+                Some(probe) => probe,
+                None => continue,
+            };
+
+            if claimed(probe.id.vendor, probe.id.product) {
+                unsafe { libc::close(probe.fd) };
+                reject(&probe.path, "driven by another backend");
+                continue;
+            }
+
+            // The motion sensors or touchpad of a controller can show up a
+            // scan after it
+            if let Some((_, gamepad)) = self
+                .gamepads
+                .iter_mut()
+                .find(|(_, gamepad)| gamepad.group == probe.group)
+            {
+                unsafe { gamepad.add_node(probe) };
+                continue;
+            }
+
+            match groups
+                .iter_mut()
+                .find(|group| group[0].group == probe.group)
+            {
+                Some(group) => group.push(probe),
+                None => groups.push(vec![probe]),
             }
         }
+
+        let mut keys = vec![];
+        for probes in groups {
+            if let Some(gamepad) = unsafe { open_joystick_device(mappings, probes) } {
+                keys.push(self.next_key);
+                self.gamepads.push((self.next_key, gamepad));
+                self.next_key += 1;
+            }
+        }
+        Ok(keys)
     }
 
-    pub fn info(&self, index: usize) -> ControllerInfo {
-        if let Some(ref gamepad) = self.gamepads.get(index) {
-            gamepad.info.clone()
-        } else {
-            ControllerInfo::new()
+    fn poll(&mut self) {
+        for (_, gamepad) in &mut self.gamepads {
+            gamepad.state.begin_update();
+            unsafe {
+                gamepad.poll();
+            }
         }
     }
-    pub fn state(&self, index: usize) -> &ControllerState {
-        if let Some(ref gamepad) = self.gamepads.get(index) {
-            &gamepad.state
-        } else {
-            &DEFAULT_CONTROLLER_STATE
+
+    fn close(&mut self, device: usize) {
+        if let Some(index) = self.gamepads.iter().position(|(key, _)| *key == device) {
+            let (_, gamepad) = self.gamepads.remove(index);
+            self.scanner
+                .forget(gamepad.nodes.iter().map(|node| node.path.as_path()));
         }
     }
+
+    fn info(&self, device: usize) -> Option<&ControllerInfo> {
+        self.gamepad(device).map(|gamepad| &gamepad.info)
+    }
+
+    fn state(&self, device: usize) -> Option<&ControllerState> {
+        self.gamepad(device).map(|gamepad| &gamepad.state)
+    }
+
+    fn raw_input(&self, device: usize) -> Option<RawInput> {
+        self.gamepad(device).map(|gamepad| gamepad.raw.clone())
+    }
+
+    fn remap(&mut self, mappings: &Mappings) {
+        for (_, gamepad) in &mut self.gamepads {
+            let (mapping, origin) = match mappings.get(gamepad.info.guid) {
+                Some(found) => found,
+                None => continue,
+            };
+            if mapping != gamepad.mapping || origin != gamepad.info.mapping {
+                gamepad.set_mapping(mapping, origin);
+                report(Diagnostic::mapping(&gamepad.info));
+            }
+        }
+    }
+}
+
+/// The backends of Linux, the Steam Deck first so that evdev leaves it alone
+pub(crate) fn backends() -> Result<Vec<Box<dyn Backend>>, Error> {
+    Ok(vec![
+        Box::new(SteamDeckBackend::new()),
+        Box::new(EvdevBackend::new()),
+    ])
 }
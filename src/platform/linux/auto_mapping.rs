@@ -0,0 +1,300 @@
+//! Mapping for controllers the database does not know, from what the evdev
+//! codes they report mean. Follows SDL's `LINUX_JoystickGetGamepadMapping`.
+
+use super::linux_input::*;
+use super::{button_codes, is_bit_set};
+use crate::mapping::{AxisRange, Binding, Mapping, Platform, Source, Target};
+use crate::mapping::{HAT_DOWN, HAT_LEFT, HAT_RIGHT, HAT_UP};
+use crate::{GamepadAxis, GamepadButton, Guid};
+
+const USB_VENDOR_SONY: u16 = 0x054c;
+
+/// Where the codes of a controller land once numbered like SDL does, from
+/// the `EVIOCGBIT` bits of its `EV_KEY` and `EV_ABS` codes
+struct Layout<'a> {
+    key_bits: &'a [u8],
+    abs_bits: &'a [u8],
+}
+
+impl Layout<'_> {
+    /// Index of a `BTN_*` or `KEY_*` code among the buttons
+    fn button(&self, code: libc::c_int) -> Option<usize> {
+        is_bit_set(code as usize, self.key_bits).then(|| {
+            button_codes()
+                .take_while(|other| *other != code)
+                .filter(|other| is_bit_set(*other as usize, self.key_bits))
+                .count()
+        })
+    }
+
+    /// Index of an `ABS_*` code among the axes, hats are not axes
+    fn axis(&self, code: libc::c_int) -> Option<usize> {
+        let is_axis = |code: libc::c_int| !is_hat(code) && is_bit_set(code as usize, self.abs_bits);
+        is_axis(code).then(|| (0..code).filter(|other| is_axis(*other)).count())
+    }
+
+    /// Index of hat `ABS_HATnX`/`ABS_HATnY` among the hats
+    fn hat(&self, hat: usize) -> Option<usize> {
+        let is_hat = |hat: usize| {
+            let code = ABS_HAT0X as usize + hat * 2;
+            is_bit_set(code, self.abs_bits) || is_bit_set(code + 1, self.abs_bits)
+        };
+        is_hat(hat).then(|| (0..hat).filter(|other| is_hat(*other)).count())
+    }
+}
+
+fn is_hat(code: libc::c_int) -> bool {
+    (ABS_HAT0X..=ABS_HAT3Y).contains(&code)
+}
+
+/// The mapping for a controller with the `EV_KEY` and `EV_ABS` codes of
+/// `key_bits` and `abs_bits`. `None` for devices that do not report
+/// `BTN_GAMEPAD`, whose codes do not say which button is which.
+pub fn create_mapping(
+    guid: Guid,
+    name: &str,
+    vendor: u16,
+    key_bits: &[u8],
+    abs_bits: &[u8],
+) -> Option<Mapping> {
+    use GamepadButton::*;
+
+    let layout = Layout { key_bits, abs_bits };
+    layout.button(BTN_GAMEPAD)?;
+
+    let mut bindings = vec![];
+    let mut button = |target: GamepadButton, code: libc::c_int| -> bool {
+        match layout.button(code) {
+            Some(index) => {
+                bindings.push(Binding {
+                    target: Target::Button(target),
+                    source: Source::Button(index),
+                });
+                true
+            }
+            None => false,
+        }
+    };
+
+    button(A, BTN_A);
+    button(B, BTN_B);
+    // Xbox controllers report X and Y as BTN_X and BTN_Y, PlayStation ones
+    // report square and triangle as BTN_WEST and BTN_NORTH
+    if vendor == USB_VENDOR_SONY {
+        button(X, BTN_WEST);
+        button(Y, BTN_NORTH);
+    } else {
+        button(X, BTN_X);
+        button(Y, BTN_Y);
+    }
+    // Some pads send keys rather than buttons for these, the Xbox ones over
+    // Bluetooth or TV remotes with a gamepad mode
+    if !button(Back, BTN_SELECT) {
+        button(Back, KEY_BACK);
+    }
+    if !button(Start, BTN_START) {
+        button(Start, KEY_MENU);
+    }
+    if !button(Guide, BTN_MODE) {
+        button(Guide, KEY_HOMEPAGE);
+    }
+    // The Share button of the Xbox Series controllers
+    button(Misc1, KEY_RECORD);
+    button(BumperLeft, BTN_TL);
+    button(BumperRight, BTN_TR);
+    button(ThumbLeft, BTN_THUMBL);
+    button(ThumbRight, BTN_THUMBR);
+
+    // The dpad as buttons, as xpad's dpad_to_buttons ones, or as a hat
+    let dpad = button(DpadUp, BTN_DPAD_UP)
+        | button(DpadDown, BTN_DPAD_DOWN)
+        | button(DpadLeft, BTN_DPAD_LEFT)
+        | button(DpadRight, BTN_DPAD_RIGHT);
+    let dpad = dpad
+        || button(DpadLeft, BTN_TRIGGER_HAPPY1)
+            | button(DpadRight, BTN_TRIGGER_HAPPY2)
+            | button(DpadUp, BTN_TRIGGER_HAPPY3)
+            | button(DpadDown, BTN_TRIGGER_HAPPY4);
+    if !dpad {
+        if let Some(index) = layout.hat(0) {
+            for (mask, target) in [
+                (HAT_UP, DpadUp),
+                (HAT_DOWN, DpadDown),
+                (HAT_LEFT, DpadLeft),
+                (HAT_RIGHT, DpadRight),
+            ] {
+                bindings.push(Binding {
+                    target: Target::Button(target),
+                    source: Source::Hat { index, mask },
+                });
+            }
+        }
+    }
+
+    let mut axis = |target: GamepadAxis, code: libc::c_int| -> bool {
+        match layout.axis(code) {
+            Some(index) => {
+                bindings.push(Binding {
+                    target: Target::Axis {
+                        axis: target,
+                        range: AxisRange::Full,
+                    },
+                    source: Source::Axis {
+                        index,
+                        range: AxisRange::Full,
+                        inverted: false,
+                    },
+                });
+                true
+            }
+            None => false,
+        }
+    };
+
+    axis(GamepadAxis::LeftX, ABS_X);
+    axis(GamepadAxis::LeftY, ABS_Y);
+    let right_stick = layout.axis(ABS_RX).is_some() && layout.axis(ABS_RY).is_some();
+    if right_stick {
+        axis(GamepadAxis::RightX, ABS_RX);
+        axis(GamepadAxis::RightY, ABS_RY);
+        axis(GamepadAxis::LeftTrigger, ABS_Z);
+        axis(GamepadAxis::RightTrigger, ABS_RZ);
+    } else {
+        // Generic HID pads put the right stick on Z and RZ
+        axis(GamepadAxis::RightX, ABS_Z);
+        axis(GamepadAxis::RightY, ABS_RZ);
+    }
+
+    // Digital triggers when there are no analog ones
+    for (trigger, code) in [
+        (GamepadAxis::LeftTrigger, BTN_TL2),
+        (GamepadAxis::RightTrigger, BTN_TR2),
+    ] {
+        let analog = bindings
+            .iter()
+            .any(|binding| matches!(binding.target, Target::Axis { axis, .. } if axis == trigger));
+        if let (false, Some(index)) = (analog, layout.button(code)) {
+            bindings.push(Binding {
+                target: Target::Axis {
+                    axis: trigger,
+                    range: AxisRange::Full,
+                },
+                source: Source::Button(index),
+            });
+        }
+    }
+
+    let mut mapping = Mapping::new(guid);
+    mapping.name = name.to_owned();
+    mapping.platform = Some(Platform::Linux);
+    mapping.bindings = bindings;
+    Some(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(vendor: u16, keys: &[libc::c_int], abs: &[libc::c_int]) -> Option<String> {
+        let mut key_bits = [0u8; (KEY_CNT as usize).div_ceil(8)];
+        let mut abs_bits = [0u8; (ABS_CNT as usize).div_ceil(8)];
+        for code in keys {
+            key_bits[*code as usize / 8] |= 1 << (code % 8);
+        }
+        for code in abs {
+            abs_bits[*code as usize / 8] |= 1 << (code % 8);
+        }
+        let guid = Guid::from_ids(3, vendor, 0x1234, 0x0100);
+        create_mapping(guid, "Pad", vendor, &key_bits, &abs_bits).map(|mapping| mapping.to_string())
+    }
+
+    const STICKS: [libc::c_int; 8] = [
+        ABS_X, ABS_Y, ABS_Z, ABS_RX, ABS_RY, ABS_RZ, ABS_HAT0X, ABS_HAT0Y,
+    ];
+
+    #[test]
+    fn xpad() {
+        let keys = [
+            BTN_A, BTN_B, BTN_X, BTN_Y, BTN_TL, BTN_TR, BTN_SELECT, BTN_START, BTN_MODE,
+            BTN_THUMBL, BTN_THUMBR,
+        ];
+        // Like the database entries for Xbox 360 controllers
+        assert_eq!(
+            mapping(0x045e, &keys, &STICKS).unwrap(),
+            "030000005e0400003412000000010000,Pad,a:b0,b:b1,back:b6,dpdown:h0.4,dpleft:h0.8,dpright:h0.2,dpup:h0.1,guide:b8,leftshoulder:b4,leftstick:b9,lefttrigger:a2,leftx:a0,lefty:a1,rightshoulder:b5,rightstick:b10,righttrigger:a5,rightx:a3,righty:a4,start:b7,x:b2,y:b3,platform:Linux,"
+        );
+    }
+
+    #[test]
+    fn hid_sony() {
+        let keys = [
+            BTN_SOUTH, BTN_EAST, BTN_NORTH, BTN_WEST, BTN_TL, BTN_TR, BTN_TL2, BTN_TR2, BTN_SELECT,
+            BTN_START, BTN_MODE, BTN_THUMBL, BTN_THUMBR,
+        ];
+        // Like the database entry for the DualShock 4 with hid-sony, square
+        // and triangle swapped compared to Xbox pads and the analog triggers
+        // winning over the digital ones
+        assert_eq!(
+            mapping(USB_VENDOR_SONY, &keys, &STICKS).unwrap(),
+            "030000004c0500003412000000010000,Pad,a:b0,b:b1,back:b8,dpdown:h0.4,dpleft:h0.8,dpright:h0.2,dpup:h0.1,guide:b10,leftshoulder:b4,leftstick:b11,lefttrigger:a2,leftx:a0,lefty:a1,rightshoulder:b5,rightstick:b12,righttrigger:a5,rightx:a3,righty:a4,start:b9,x:b3,y:b2,platform:Linux,"
+        );
+    }
+
+    #[test]
+    fn keys_below_btn_misc() {
+        let keys = [
+            BTN_A,
+            BTN_B,
+            BTN_X,
+            BTN_Y,
+            BTN_TL,
+            BTN_TR,
+            BTN_START,
+            BTN_THUMBL,
+            BTN_THUMBR,
+            KEY_BACK,
+            KEY_HOMEPAGE,
+            KEY_RECORD,
+        ];
+        // An Xbox controller over Bluetooth. The keys are numbered after the
+        // 9 buttons from BTN_JOYSTICK on, in the order of their codes.
+        let mapping = mapping(0x045e, &keys, &STICKS).unwrap();
+        assert_eq!(
+            mapping,
+            "030000005e0400003412000000010000,Pad,a:b0,b:b1,back:b9,dpdown:h0.4,dpleft:h0.8,dpright:h0.2,dpup:h0.1,guide:b11,leftshoulder:b4,leftstick:b7,lefttrigger:a2,leftx:a0,lefty:a1,misc1:b10,rightshoulder:b5,rightstick:b8,righttrigger:a5,rightx:a3,righty:a4,start:b6,x:b2,y:b3,platform:Linux,"
+        );
+    }
+
+    #[test]
+    fn generic_hid() {
+        let keys = [
+            BTN_SOUTH,
+            BTN_EAST,
+            BTN_NORTH,
+            BTN_WEST,
+            BTN_TL,
+            BTN_TR,
+            BTN_TL2,
+            BTN_TR2,
+            BTN_SELECT,
+            BTN_START,
+            BTN_THUMBL,
+            BTN_THUMBR,
+            BTN_TRIGGER_HAPPY1,
+            BTN_TRIGGER_HAPPY2,
+            BTN_TRIGGER_HAPPY3,
+            BTN_TRIGGER_HAPPY4,
+        ];
+        // Right stick on Z and RZ, d-pad and triggers as buttons
+        assert_eq!(
+            mapping(0x0079, &keys, &[ABS_X, ABS_Y, ABS_Z, ABS_RZ]).unwrap(),
+            "03000000790000003412000000010000,Pad,a:b0,b:b1,back:b8,dpdown:b15,dpleft:b12,dpright:b13,dpup:b14,leftshoulder:b4,leftstick:b10,lefttrigger:b6,leftx:a0,lefty:a1,rightshoulder:b5,rightstick:b11,righttrigger:b7,rightx:a2,righty:a3,start:b9,x:b2,y:b3,platform:Linux,"
+        );
+
+        // A joystick, whose buttons mean nothing in particular
+        assert_eq!(
+            mapping(0x0079, &[BTN_TRIGGER, BTN_THUMB], &[ABS_X, ABS_Y]),
+            None
+        );
+    }
+}
@@ -73,9 +73,9 @@ pub use self::consts::*;
 #[doc(hidden)]
 pub const NRSHIFT: u32 = 0;
 #[doc(hidden)]
-pub const TYPESHIFT: u32 = NRSHIFT + NRBITS as u32;
+pub const TYPESHIFT: u32 = NRSHIFT + NRBITS;
 #[doc(hidden)]
-pub const SIZESHIFT: u32 = TYPESHIFT + TYPEBITS as u32;
+pub const SIZESHIFT: u32 = TYPESHIFT + TYPEBITS;
 #[doc(hidden)]
 pub const DIRSHIFT: u32 = SIZESHIFT + SIZEBITS as u32;
 
@@ -169,6 +169,18 @@ pub const fn eviocgname(len: u32) -> u64 {
     ioc(READ as _, b'E' as _, 0x06, len as _)
 }
 
+pub const fn eviocgphys(len: u32) -> u64 {
+    ioc(READ as _, b'E' as _, 0x07, len as _)
+}
+
+pub const fn eviocguniq(len: u32) -> u64 {
+    ioc(READ as _, b'E' as _, 0x08, len as _)
+}
+
+pub const fn eviocgprop(len: u32) -> u64 {
+    ioc(READ as _, b'E' as _, 0x09, len as _)
+}
+
 pub const fn eviocgabs(abs: u32) -> u64 {
     ior(
         b'E' as _,
@@ -176,3 +188,58 @@ pub const fn eviocgabs(abs: u32) -> u64 {
         std::mem::size_of::<InputAbsInfo>() as _,
     )
 }
+
+// and some part from linux/source/include/uapi/linux/hidraw.h
+
+pub const HID_MAX_DESCRIPTOR_SIZE: usize = 4096;
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct HidrawDevinfo {
+    pub bustype: u32,
+    pub vendor: i16,
+    pub product: i16,
+}
+
+#[repr(C)]
+pub struct HidrawReportDescriptor {
+    pub size: u32,
+    pub value: [u8; HID_MAX_DESCRIPTOR_SIZE],
+}
+impl Default for HidrawReportDescriptor {
+    fn default() -> Self {
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+pub const fn hidiocgrdescsize() -> u64 {
+    ior(b'H' as _, 0x01, std::mem::size_of::<libc::c_int>() as _)
+}
+
+pub const fn hidiocgrdesc() -> u64 {
+    ior(
+        b'H' as _,
+        0x02,
+        std::mem::size_of::<HidrawReportDescriptor>() as _,
+    )
+}
+
+pub const fn hidiocgrawinfo() -> u64 {
+    ior(b'H' as _, 0x03, std::mem::size_of::<HidrawDevinfo>() as _)
+}
+
+pub const fn hidiocgrawphys(len: u32) -> u64 {
+    ioc(READ as _, b'H' as _, 0x05, len as _)
+}
+
+pub const fn hidiocgrawuniq(len: u32) -> u64 {
+    ioc(READ as _, b'H' as _, 0x08, len as _)
+}
+
+pub const fn hidiocsfeature(len: u32) -> u64 {
+    iorw(b'H' as _, 0x06, len as _)
+}
+
+pub const fn hidiocgfeature(len: u32) -> u64 {
+    iorw(b'H' as _, 0x07, len as _)
+}
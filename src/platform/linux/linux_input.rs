@@ -3,6 +3,23 @@
 use libc::c_int;
 
 // https://docs.rs/input-linux-sys/0.6.0/src/input_linux_sys/events.rs.html
+pub const INPUT_PROP_POINTER: c_int = 0x00;
+pub const INPUT_PROP_DIRECT: c_int = 0x01;
+pub const INPUT_PROP_BUTTONPAD: c_int = 0x02;
+pub const INPUT_PROP_SEMI_MT: c_int = 0x03;
+pub const INPUT_PROP_TOPBUTTONPAD: c_int = 0x04;
+pub const INPUT_PROP_POINTING_STICK: c_int = 0x05;
+pub const INPUT_PROP_ACCELEROMETER: c_int = 0x06;
+pub const INPUT_PROP_MAX: c_int = 0x1f;
+pub const INPUT_PROP_CNT: c_int = INPUT_PROP_MAX + 1;
+
+pub const BUS_PCI: c_int = 0x01;
+pub const BUS_ISAPNP: c_int = 0x02;
+pub const BUS_USB: c_int = 0x03;
+pub const BUS_HIL: c_int = 0x04;
+pub const BUS_BLUETOOTH: c_int = 0x05;
+pub const BUS_VIRTUAL: c_int = 0x06;
+
 pub const EV_SYN: c_int = 0x00;
 pub const EV_KEY: c_int = 0x01;
 pub const EV_REL: c_int = 0x02;
@@ -24,6 +41,14 @@ pub const KEY_CNT: c_int = KEY_MAX + 1;
 pub const ABS_MAX: c_int = 0x3f;
 pub const ABS_CNT: c_int = ABS_MAX + 1;
 
+/*
+ * Keys some gamepads use for their Select, Guide, Start and Share buttons
+ */
+pub const KEY_MENU: c_int = 139;
+pub const KEY_BACK: c_int = 158;
+pub const KEY_RECORD: c_int = 167;
+pub const KEY_HOMEPAGE: c_int = 172;
+
 pub const BTN_MISC: c_int = 0x100;
 pub const BTN_0: c_int = 0x100;
 pub const BTN_1: c_int = 0x101;
@@ -103,6 +128,17 @@ pub const BTN_TOOL_QUADTAP: c_int = 0x14f; /* Four fingers on trackpad */
 pub const BTN_WHEEL: c_int = 0x150;
 pub const BTN_GEAR_DOWN: c_int = 0x150;
 pub const BTN_GEAR_UP: c_int = 0x151;
+
+pub const BTN_DPAD_UP: c_int = 0x220;
+pub const BTN_DPAD_DOWN: c_int = 0x221;
+pub const BTN_DPAD_LEFT: c_int = 0x222;
+pub const BTN_DPAD_RIGHT: c_int = 0x223;
+
+pub const BTN_TRIGGER_HAPPY: c_int = 0x2c0;
+pub const BTN_TRIGGER_HAPPY1: c_int = 0x2c0;
+pub const BTN_TRIGGER_HAPPY2: c_int = 0x2c1;
+pub const BTN_TRIGGER_HAPPY3: c_int = 0x2c2;
+pub const BTN_TRIGGER_HAPPY4: c_int = 0x2c3;
 /*
  * Absolute axes
  */
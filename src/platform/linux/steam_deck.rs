@@ -0,0 +1,679 @@
+// Steam Deck built-in controller over hidraw.
+//
+// Without Steam running most of the Deck's controls never reach evdev, so the
+// controller interface is read directly. While Steam runs it owns the Deck,
+// and games get its virtual gamepad over evdev instead. Report layout and
+// settings are from
+// https://github.com/libsdl-org/SDL/blob/main/src/joystick/hidapi/SDL_hidapi_steamdeck.c
+// and https://github.com/torvalds/linux/blob/master/drivers/hid/hid-steam.c
+
+use crate::diagnostic::report;
+use crate::mapping::{MappingLayer, MappingOrigin, Mappings};
+use crate::{
+    Backend, ControllerInfo, ControllerState, ControllerStatus, ControllerType, Diagnostic, Error,
+    GamepadAxis, GamepadButton, Guid,
+};
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::ioctl::{self, HidrawDevinfo, HidrawReportDescriptor};
+use super::{Scanner, SCAN_INTERVAL};
+
+pub const VALVE_VENDOR_ID: u16 = 0x28de;
+pub const STEAM_DECK_PRODUCT_ID: u16 = 0x1205;
+
+const HID_FEATURE_REPORT_BYTES: usize = 64;
+
+const ID_CONTROLLER_DECK_STATE: u8 = 0x09;
+const ID_CLEAR_DIGITAL_MAPPINGS: u8 = 0x81;
+const ID_SET_SETTINGS_VALUES: u8 = 0x87;
+
+const SETTING_LEFT_TRACKPAD_MODE: u8 = 7;
+const SETTING_RIGHT_TRACKPAD_MODE: u8 = 8;
+const SETTING_SMOOTH_ABSOLUTE_MOUSE: u8 = 24;
+const SETTING_LEFT_TRACKPAD_CLICK_PRESSURE: u8 = 52;
+const SETTING_RIGHT_TRACKPAD_CLICK_PRESSURE: u8 = 53;
+
+const TRACKPAD_NONE: u16 = 7;
+
+// The controller falls back to lizard mode unless it is told otherwise every
+// now and then, SDL re-sends the settings every 200 reports.
+const LIZARD_MODE_INTERVAL: usize = 200;
+
+const ACCEL_RES_PER_G: f32 = 16384.0;
+const GYRO_RES_PER_DEGREE: f32 = 16.0;
+
+// Bits 0 and 1 are R2 and L2 pulled, the digital triggers come from the
+// trigger axes instead
+pub const BUTTON_R: u64 = 1 << 2;
+pub const BUTTON_L: u64 = 1 << 3;
+pub const BUTTON_Y: u64 = 1 << 4;
+pub const BUTTON_B: u64 = 1 << 5;
+pub const BUTTON_X: u64 = 1 << 6;
+pub const BUTTON_A: u64 = 1 << 7;
+pub const BUTTON_DPAD_UP: u64 = 1 << 8;
+pub const BUTTON_DPAD_RIGHT: u64 = 1 << 9;
+pub const BUTTON_DPAD_LEFT: u64 = 1 << 10;
+pub const BUTTON_DPAD_DOWN: u64 = 1 << 11;
+pub const BUTTON_VIEW: u64 = 1 << 12;
+pub const BUTTON_STEAM: u64 = 1 << 13;
+pub const BUTTON_MENU: u64 = 1 << 14;
+pub const BUTTON_L5: u64 = 1 << 15;
+pub const BUTTON_R5: u64 = 1 << 16;
+pub const BUTTON_LEFT_PAD: u64 = 1 << 17;
+pub const BUTTON_RIGHT_PAD: u64 = 1 << 18;
+pub const BUTTON_LEFT_PAD_TOUCH: u64 = 1 << 19;
+pub const BUTTON_RIGHT_PAD_TOUCH: u64 = 1 << 20;
+pub const BUTTON_L3: u64 = 1 << 22;
+pub const BUTTON_R3: u64 = 1 << 26;
+pub const BUTTON_L4: u64 = 1 << 41;
+pub const BUTTON_R4: u64 = 1 << 42;
+pub const BUTTON_LEFT_STICK_TOUCH: u64 = 1 << 46;
+pub const BUTTON_RIGHT_STICK_TOUCH: u64 = 1 << 47;
+pub const BUTTON_QUICK_ACCESS: u64 = 1 << 50;
+
+const BUTTONS: [(u64, GamepadButton); 20] = [
+    (BUTTON_A, GamepadButton::A),
+    (BUTTON_B, GamepadButton::B),
+    (BUTTON_X, GamepadButton::X),
+    (BUTTON_Y, GamepadButton::Y),
+    (BUTTON_DPAD_UP, GamepadButton::DpadUp),
+    (BUTTON_DPAD_DOWN, GamepadButton::DpadDown),
+    (BUTTON_DPAD_RIGHT, GamepadButton::DpadRight),
+    (BUTTON_DPAD_LEFT, GamepadButton::DpadLeft),
+    (BUTTON_L, GamepadButton::BumperLeft),
+    (BUTTON_R, GamepadButton::BumperRight),
+    (BUTTON_L3, GamepadButton::ThumbLeft),
+    (BUTTON_R3, GamepadButton::ThumbRight),
+    (BUTTON_VIEW, GamepadButton::Back),
+    (BUTTON_MENU, GamepadButton::Start),
+    (BUTTON_STEAM, GamepadButton::Guide),
+    (BUTTON_QUICK_ACCESS, GamepadButton::Misc1),
+    (BUTTON_R4, GamepadButton::Paddle1),
+    (BUTTON_L4, GamepadButton::Paddle2),
+    (BUTTON_R5, GamepadButton::Paddle3),
+    (BUTTON_L5, GamepadButton::Paddle4),
+];
+
+/// One decoded `ID_CONTROLLER_DECK_STATE` input report.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Report {
+    pub packet_num: u32,
+    pub buttons: u64,
+    pub left_pad: [i16; 2],
+    pub right_pad: [i16; 2],
+    pub accel: [i16; 3],
+    pub gyro: [i16; 3],
+    pub left_trigger: u16,
+    pub right_trigger: u16,
+    pub left_stick: [i16; 2],
+    pub right_stick: [i16; 2],
+    pub left_pad_pressure: u16,
+    pub right_pad_pressure: u16,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_i16(data: &[u8], offset: usize) -> i16 {
+    read_u16(data, offset) as i16
+}
+
+impl Report {
+    pub fn parse(data: &[u8]) -> Option<Report> {
+        // 4 bytes of header: report version, report type and payload length
+        if data.len() < 60 || read_u16(data, 0) != 0x0001 || data[2] != ID_CONTROLLER_DECK_STATE {
+            return None;
+        }
+
+        let mut buttons = [0; 8];
+        buttons.copy_from_slice(&data[8..16]);
+
+        Some(Report {
+            packet_num: u32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+            buttons: u64::from_le_bytes(buttons),
+            left_pad: [read_i16(data, 16), read_i16(data, 18)],
+            right_pad: [read_i16(data, 20), read_i16(data, 22)],
+            accel: [read_i16(data, 24), read_i16(data, 26), read_i16(data, 28)],
+            gyro: [read_i16(data, 30), read_i16(data, 32), read_i16(data, 34)],
+            // 36..44 is the gyro quaternion, not exposed
+            left_trigger: read_u16(data, 44),
+            right_trigger: read_u16(data, 46),
+            left_stick: [read_i16(data, 48), read_i16(data, 50)],
+            right_stick: [read_i16(data, 52), read_i16(data, 54)],
+            left_pad_pressure: read_u16(data, 56),
+            right_pad_pressure: read_u16(data, 58),
+        })
+    }
+
+    pub fn is_set(&self, button: u64) -> bool {
+        self.buttons & button != 0
+    }
+}
+
+/// Feature reports, with the leading report number hidraw expects, that turn
+/// off the trackpad mouse emulation and the haptic trackpad clicks.
+pub fn lizard_mode_reports() -> [[u8; HID_FEATURE_REPORT_BYTES + 1]; 2] {
+    let mut clear_mappings = [0; HID_FEATURE_REPORT_BYTES + 1];
+    clear_mappings[1] = ID_CLEAR_DIGITAL_MAPPINGS;
+
+    let settings = [
+        (SETTING_SMOOTH_ABSOLUTE_MOUSE, 0),
+        (SETTING_LEFT_TRACKPAD_MODE, TRACKPAD_NONE),
+        (SETTING_RIGHT_TRACKPAD_MODE, TRACKPAD_NONE),
+        (SETTING_LEFT_TRACKPAD_CLICK_PRESSURE, 0xffff),
+        (SETTING_RIGHT_TRACKPAD_CLICK_PRESSURE, 0xffff),
+    ];
+
+    let mut set_settings = [0; HID_FEATURE_REPORT_BYTES + 1];
+    set_settings[1] = ID_SET_SETTINGS_VALUES;
+    set_settings[2] = (settings.len() * 3) as u8;
+    for (i, (setting, value)) in settings.iter().enumerate() {
+        let offset = 3 + i * 3;
+        set_settings[offset] = *setting;
+        set_settings[offset + 1..offset + 3].copy_from_slice(&value.to_le_bytes());
+    }
+
+    [clear_mappings, set_settings]
+}
+
+fn stick_value(value: i16) -> f32 {
+    (value as f32 / 32767.0).clamp(-1.0, 1.0)
+}
+
+fn unit_value(value: u16) -> f32 {
+    (value as f32 / 32767.0).min(1.0)
+}
+
+/// Info of a Deck on `bustype`, but for what only its node knows
+fn deck_info(bustype: u16) -> ControllerInfo {
+    // hidraw reports no version, so the GUID is made of the bus and Valve's
+    // ids with version 0, and carries the name CRC like the evdev ones
+    let name = "Steam Deck";
+    let guid = Guid::from_ids(bustype, VALVE_VENDOR_ID, STEAM_DECK_PRODUCT_ID, 0)
+        .with_name_crc(name.as_bytes());
+
+    ControllerInfo {
+        name: name.to_owned(),
+        buttons: BUTTONS
+            .iter()
+            .map(|(_, button)| *button)
+            .chain([GamepadButton::TriggerLeft, GamepadButton::TriggerRight])
+            .collect(),
+        analog_count: 6,
+        touchpad_count: 2,
+        has_motion: true,
+        vendor_id: VALVE_VENDOR_ID,
+        product_id: STEAM_DECK_PRODUCT_ID,
+        bus: super::bus_type(bustype),
+        guid,
+        controller_type: ControllerType::SteamDeck,
+        mapping: MappingOrigin::new(MappingLayer::Native),
+        ..ControllerInfo::new()
+    }
+}
+
+pub struct SteamDeck {
+    pub path: PathBuf,
+    fd: libc::c_int,
+    pub info: ControllerInfo,
+    pub state: ControllerState,
+    reports_since_lizard_mode: usize,
+}
+
+impl SteamDeck {
+    pub unsafe fn open(path: &Path) -> Option<SteamDeck> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let filename = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+        let fd = libc::open(filename.as_ptr(), libc::O_RDWR | libc::O_NONBLOCK);
+        if fd == -1 {
+            return None;
+        }
+
+        let bustype = match deck_bustype(fd) {
+            Some(bustype) => bustype,
+            None => {
+                libc::close(fd);
+                return None;
+            }
+        };
+
+        let mut deck = SteamDeck {
+            path: path.to_owned(),
+            fd,
+            info: ControllerInfo {
+                serial: super::read_string(fd, ioctl::hidiocgrawuniq),
+                physical_path: super::read_string(fd, ioctl::hidiocgrawphys),
+                ..deck_info(bustype)
+            },
+            state: ControllerState::new(),
+            reports_since_lizard_mode: 0,
+        };
+        deck.state.status = ControllerStatus::Connected;
+        deck.disable_lizard_mode();
+
+        report(Diagnostic::DeviceFound {
+            name: deck.info.name.clone(),
+            guid: deck.info.guid,
+            path: Some(path.display().to_string()),
+        });
+        report(Diagnostic::mapping(&deck.info));
+        Some(deck)
+    }
+
+    unsafe fn disable_lizard_mode(&mut self) {
+        self.reports_since_lizard_mode = 0;
+
+        for report in lizard_mode_reports().iter_mut() {
+            libc::ioctl(
+                self.fd,
+                ioctl::hidiocsfeature(report.len() as _),
+                report.as_mut_ptr(),
+            );
+        }
+
+        // There may be a lingering report read back after changing settings
+        let mut discard = [0u8; HID_FEATURE_REPORT_BYTES + 1];
+        libc::ioctl(
+            self.fd,
+            ioctl::hidiocgfeature(discard.len() as _),
+            discard.as_mut_ptr(),
+        );
+    }
+
+    pub unsafe fn poll(&mut self) {
+        let mut data = [0u8; HID_FEATURE_REPORT_BYTES];
+
+        loop {
+            let len = libc::read(self.fd, data.as_mut_ptr() as *mut _, data.len());
+            if len <= 0 {
+                if len < 0 && super::device_lost(&self.path) {
+                    self.state.status = ControllerStatus::Disconnected;
+                }
+                break;
+            }
+
+            if let Some(report) = Report::parse(&data[..len as usize]) {
+                self.apply(&report);
+                self.reports_since_lizard_mode += 1;
+            }
+        }
+
+        if self.reports_since_lizard_mode > LIZARD_MODE_INTERVAL {
+            self.disable_lizard_mode();
+        }
+    }
+
+    pub fn apply(&mut self, report: &Report) {
+        let state = &mut self.state;
+
+        state.sequence = report.packet_num as usize;
+
+        for (bit, button) in BUTTONS.iter() {
+            state.set_button(*button, report.is_set(*bit));
+        }
+
+        // The Deck reports Y up, evdev reports it down
+        state.set_axis(GamepadAxis::LeftX, stick_value(report.left_stick[0]));
+        state.set_axis(GamepadAxis::LeftY, -stick_value(report.left_stick[1]));
+        state.set_axis(GamepadAxis::RightX, stick_value(report.right_stick[0]));
+        state.set_axis(GamepadAxis::RightY, -stick_value(report.right_stick[1]));
+        // The L2 and R2 bits only come at the end of the travel, the digital
+        // triggers come from the axes like on the other controllers
+        state.set_axis(GamepadAxis::LeftTrigger, unit_value(report.left_trigger));
+        state.set_axis(GamepadAxis::RightTrigger, unit_value(report.right_trigger));
+
+        let pads = [
+            (
+                report.left_pad,
+                report.left_pad_pressure,
+                BUTTON_LEFT_PAD_TOUCH,
+                BUTTON_LEFT_PAD,
+            ),
+            (
+                report.right_pad,
+                report.right_pad_pressure,
+                BUTTON_RIGHT_PAD_TOUCH,
+                BUTTON_RIGHT_PAD,
+            ),
+        ];
+        for (touchpad, (position, pressure, touch, click)) in state.touchpads.iter_mut().zip(&pads)
+        {
+            touchpad.touched = report.is_set(*touch);
+            touchpad.pressed = report.is_set(*click);
+            touchpad.x = stick_value(position[0]);
+            touchpad.y = -stick_value(position[1]);
+            touchpad.pressure = unit_value(*pressure);
+        }
+
+        state.stick_touch = [
+            report.is_set(BUTTON_LEFT_STICK_TOUCH),
+            report.is_set(BUTTON_RIGHT_STICK_TOUCH),
+        ];
+
+        for i in 0..3 {
+            state.motion.accel[i] = report.accel[i] as f32 / ACCEL_RES_PER_G;
+            state.motion.gyro[i] = report.gyro[i] as f32 / GYRO_RES_PER_DEGREE;
+        }
+    }
+}
+
+impl Drop for SteamDeck {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+// The Deck exposes keyboard and mouse interfaces with the same ids, the
+// controller is the one with a vendor defined usage page.
+/// Bus of the node of `fd` when it is the controller interface of a Deck
+unsafe fn deck_bustype(fd: libc::c_int) -> Option<u16> {
+    let mut devinfo = HidrawDevinfo::default();
+    if libc::ioctl(fd, ioctl::hidiocgrawinfo(), &mut devinfo as *mut _) < 0
+        || devinfo.vendor as u16 != VALVE_VENDOR_ID
+        || devinfo.product as u16 != STEAM_DECK_PRODUCT_ID
+        || !is_vendor_interface(fd)
+    {
+        return None;
+    }
+    Some(devinfo.bustype as u16)
+}
+
+/// Whether `path` is the controller interface of a Deck, without opening it
+/// for writing or changing its settings
+unsafe fn is_deck(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let filename = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+    let fd = libc::open(filename.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK);
+    if fd == -1 {
+        return false;
+    }
+    let is_deck = deck_bustype(fd).is_some();
+    libc::close(fd);
+    is_deck
+}
+
+unsafe fn is_vendor_interface(fd: libc::c_int) -> bool {
+    let mut size: libc::c_int = 0;
+    if libc::ioctl(fd, ioctl::hidiocgrdescsize(), &mut size as *mut _) < 0 || size < 3 {
+        return false;
+    }
+
+    let mut descriptor = HidrawReportDescriptor {
+        size: size as u32,
+        ..Default::default()
+    };
+    if libc::ioctl(fd, ioctl::hidiocgrdesc(), &mut descriptor as *mut _) < 0 {
+        return false;
+    }
+
+    // Usage Page item with a 2 byte value of 0xff00..=0xffff
+    descriptor.value[0] == 0x06 && descriptor.value[2] == 0xff
+}
+
+/// Whether a process named `steam` is running, from the `comm` files of
+/// `proc`
+fn steam_running(proc: &Path) -> bool {
+    let entries = match std::fs::read_dir(proc) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    entries.flatten().any(|entry| {
+        entry
+            .file_name()
+            .to_str()
+            .is_some_and(|pid| pid.bytes().all(|byte| byte.is_ascii_digit()))
+            && std::fs::read_to_string(entry.path().join("comm"))
+                .is_ok_and(|comm| comm.trim_end() == "steam")
+    })
+}
+
+/// The built-in controller of the Steam Deck, read from its hidraw node.
+///
+/// Steam sends the Deck its own settings, so while it runs the backend
+/// leaves the Deck alone: open Decks are reported disconnected and none are
+/// opened until Steam quits. The Deck's own evdev nodes stay claimed, the
+/// controller is Steam's virtual gamepad then.
+pub struct SteamDeckBackend {
+    decks: Vec<(usize, SteamDeck)>,
+    next_key: usize,
+    scanner: Scanner,
+    /// Checked once per `SCAN_INTERVAL`, while there is a Deck
+    steam_running: bool,
+    last_steam_check: Option<Instant>,
+}
+
+impl SteamDeckBackend {
+    pub fn new() -> SteamDeckBackend {
+        SteamDeckBackend {
+            decks: vec![],
+            next_key: 0,
+            scanner: Scanner::new("/dev", "hidraw", libc::R_OK | libc::W_OK),
+            steam_running: false,
+            last_steam_check: None,
+        }
+    }
+
+    fn deck(&self, device: usize) -> Option<&SteamDeck> {
+        self.decks
+            .iter()
+            .find(|(key, _)| *key == device)
+            .map(|(_, deck)| deck)
+    }
+}
+
+impl Default for SteamDeckBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for SteamDeckBackend {
+    fn enumerate(
+        &mut self,
+        _mappings: &Mappings,
+        _claimed: &dyn Fn(u16, u16) -> bool,
+    ) -> Result<Vec<usize>, Error> {
+        let found: Vec<PathBuf> = self
+            .scanner
+            .scan()?
+            .into_iter()
+            .filter(|path| unsafe { is_deck(path) })
+            .collect();
+        // Machines without a Deck never look through /proc
+        if (!found.is_empty() || !self.decks.is_empty())
+            && self
+                .last_steam_check
+                .is_none_or(|last_check| last_check.elapsed() >= SCAN_INTERVAL)
+        {
+            self.last_steam_check = Some(Instant::now());
+            self.steam_running = steam_running(Path::new("/proc"));
+        }
+        // Scanned again, so that the nodes are opened once Steam quits
+        if self.steam_running {
+            self.scanner.forget(found.iter().map(PathBuf::as_path));
+            return Ok(vec![]);
+        }
+
+        let mut keys = vec![];
+        for path in found {
+            if let Some(deck) = unsafe { SteamDeck::open(&path) } {
+                keys.push(self.next_key);
+                self.decks.push((self.next_key, deck));
+                self.next_key += 1;
+            }
+        }
+        Ok(keys)
+    }
+
+    fn poll(&mut self) {
+        for (_, deck) in &mut self.decks {
+            deck.state.begin_update();
+            if self.steam_running {
+                deck.state.status = ControllerStatus::Disconnected;
+                continue;
+            }
+            unsafe {
+                deck.poll();
+            }
+        }
+    }
+
+    fn close(&mut self, device: usize) {
+        if let Some(index) = self.decks.iter().position(|(key, _)| *key == device) {
+            let (_, deck) = self.decks.remove(index);
+            self.scanner.forget([deck.path.as_path()]);
+        }
+    }
+
+    fn info(&self, device: usize) -> Option<&ControllerInfo> {
+        self.deck(device).map(|deck| &deck.info)
+    }
+
+    fn state(&self, device: usize) -> Option<&ControllerState> {
+        self.deck(device).map(|deck| &deck.state)
+    }
+
+    /// Its evdev nodes lack most of the controls while Steam is not running,
+    /// and Steam's virtual gamepad stands for the Deck while it is
+    fn claims(&self, vendor_id: u16, product_id: u16) -> bool {
+        vendor_id == VALVE_VENDOR_ID
+            && product_id == STEAM_DECK_PRODUCT_ID
+            && (!self.decks.is_empty() || self.steam_running)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(hex: &str) -> Vec<u8> {
+        hex.split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+            .collect()
+    }
+
+    // Nothing touched, Deck lying flat on a table
+    const IDLE: &str = "
+        01 00 09 40 2a 1b 00 00 00 00 00 00 00 00 00 00
+        00 00 00 00 00 00 00 00 0c 00 f4 ff 00 40 01 00
+        ff ff 00 00 ff 7f 00 00 00 00 00 00 00 00 00 00
+        18 00 e8 ff 00 00 0c 00 00 00 00 00 00 00 00 00";
+
+    // A, L4 and R5 held, thumb resting on the left stick pushed fully right,
+    // right trigger fully pulled and right trackpad clicked in the top left
+    const BUTTONS_AND_PADS: &str = "
+        01 00 09 40 2b 1b 00 00 80 00 15 00 00 42 00 00
+        00 00 00 00 00 c0 00 40 00 00 00 00 00 40 20 00
+        00 00 e0 ff 00 00 00 00 00 00 00 00 00 00 ff 7f
+        ff 7f 00 00 00 00 00 00 00 00 00 30 00 00 00 00";
+
+    #[test]
+    fn parse_idle_report() {
+        let report = Report::parse(&fixture(IDLE)).unwrap();
+
+        assert_eq!(report.packet_num, 0x1b2a);
+        assert_eq!(report.buttons, 0);
+        assert_eq!(report.accel, [12, -12, 16384]);
+        assert_eq!(report.gyro, [1, -1, 0]);
+        assert_eq!(report.left_stick, [24, -24]);
+        assert_eq!(report.right_stick, [0, 12]);
+    }
+
+    #[test]
+    fn parse_buttons_and_pads() {
+        let report = Report::parse(&fixture(BUTTONS_AND_PADS)).unwrap();
+
+        assert!(report.is_set(BUTTON_A));
+        assert!(report.is_set(BUTTON_L4));
+        assert!(report.is_set(BUTTON_R5));
+        assert!(report.is_set(BUTTON_RIGHT_PAD));
+        assert!(report.is_set(BUTTON_RIGHT_PAD_TOUCH));
+        assert!(report.is_set(BUTTON_LEFT_STICK_TOUCH));
+        assert!(!report.is_set(BUTTON_B));
+        assert!(!report.is_set(BUTTON_R4));
+        assert!(!report.is_set(BUTTON_LEFT_PAD_TOUCH));
+        assert!(!report.is_set(BUTTON_RIGHT_STICK_TOUCH));
+        assert_eq!(report.right_pad, [-16384, 16384]);
+        assert_eq!(report.right_trigger, 32767);
+        assert_eq!(report.left_stick, [32767, 0]);
+        assert_eq!(report.right_pad_pressure, 12288);
+    }
+
+    #[test]
+    fn reject_other_reports() {
+        let mut data = fixture(IDLE);
+        assert!(Report::parse(&data[..32]).is_none());
+
+        // ID_CONTROLLER_STATE, sent by the wireless Steam Controller
+        data[2] = 0x01;
+        assert!(Report::parse(&data).is_none());
+    }
+
+    #[test]
+    fn info() {
+        let info = deck_info(super::super::linux_input::BUS_USB as u16);
+
+        assert_eq!(info.guid.vendor(), Some(VALVE_VENDOR_ID));
+        assert_eq!(info.guid.product(), Some(STEAM_DECK_PRODUCT_ID));
+        assert_eq!(info.guid.to_string(), "0300f617de2800000512000000000000");
+        assert_eq!(info.bus, crate::BusType::Usb);
+    }
+
+    #[test]
+    fn detect_steam() {
+        let proc = std::env::temp_dir().join(format!("quad-gamepad-proc-{}", std::process::id()));
+        let process = |pid: &str, comm: &str| {
+            std::fs::create_dir_all(proc.join(pid)).unwrap();
+            std::fs::write(proc.join(pid).join("comm"), comm).unwrap();
+        };
+
+        process("1", "systemd\n");
+        process("self", "steam\n");
+        process("40", "steamwebhelper\n");
+        assert!(!steam_running(&proc));
+        process("41", "steam\n");
+        assert!(steam_running(&proc));
+
+        std::fs::remove_dir_all(&proc).unwrap();
+        assert!(!steam_running(&proc));
+    }
+
+    #[test]
+    fn lizard_mode_settings() {
+        let [clear, set] = lizard_mode_reports();
+
+        assert_eq!(clear[..3], [0x00, ID_CLEAR_DIGITAL_MAPPINGS, 0x00]);
+        assert_eq!(
+            set[..18],
+            [
+                0x00,
+                ID_SET_SETTINGS_VALUES,
+                15, //
+                24,
+                0x00,
+                0x00, //
+                7,
+                0x07,
+                0x00, //
+                8,
+                0x07,
+                0x00, //
+                52,
+                0xff,
+                0xff, //
+                53,
+                0xff,
+                0xff,
+            ]
+        );
+        assert!(set[18..].iter().all(|byte| *byte == 0));
+    }
+}
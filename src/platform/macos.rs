@@ -1,161 +1,229 @@
 mod hid;
 mod io_kit;
 
-use super::super::{
-    ControllerInfo, ControllerState, ControllerStatus, DEFAULT_CONTROLLER_STATE, MAX_ANALOG,
-    MAX_DEVICES, MAX_DIGITAL,
-};
+use super::super::{BusType, ControllerInfo, ControllerState, ControllerStatus, ControllerType};
+
+use crate::diagnostic::report;
+use crate::mapping::{AxisMatches, Mapping, MappingOrigin, Mappings};
+use crate::{Backend, Diagnostic, Error, RawInput};
+
+use std::cell::RefCell;
+use std::rc::Weak;
+
+struct Controller {
+    key: usize,
+    device: Weak<RefCell<hid::Device>>,
+    info: ControllerInfo,
+    state: ControllerState,
+    mapping: Mapping,
+    axis_matches: AxisMatches,
+}
 
-use crate::mapping::Mapping;
+impl Controller {
+    /// The mapping of `device` in `mappings`, or the default one
+    fn find_mapping(mappings: &Mappings, device: &hid::Device) -> (Mapping, MappingOrigin) {
+        mappings
+            .get(device.guid)
+            .unwrap_or_else(|| (Mapping::new(device.guid), MappingOrigin::default()))
+    }
 
-pub struct ControllerContext {
-    info: Vec<ControllerInfo>,
-    state: Vec<ControllerState>,
-    hid: hid::HID,
-    mappings: crate::mapping::MappingsMap,
+    fn set_mapping(&mut self, mapping: Mapping, origin: MappingOrigin, device: &hid::Device) {
+        let (buttons, axes, hats) = (device.buttons.len(), device.axes.len(), device.hats.len());
+        self.info.buttons = mapping.buttons(buttons, axes, hats);
+        self.info.analog_count = mapping.analog_count(buttons, axes, hats);
+        self.info.face_style = mapping.face_style(self.info.controller_type);
+        self.mapping = mapping;
+        self.info.mapping = origin;
+        self.mapping
+            .apply_raw(&raw_input(device), &mut self.state, &mut self.axis_matches);
+        report(Diagnostic::mapping(&self.info));
+    }
 }
 
-// Helper function for convert Vec to array
-fn to_digital_state_array(mapping: &Mapping, state: &Vec<bool>) -> [bool; MAX_DIGITAL] {
-    let mut arr = [false; MAX_DIGITAL];
-    for i in 0..state.len() {
-        arr[mapping.buttons[i] as usize] = state[i];
+fn raw_input(device: &hid::Device) -> RawInput {
+    RawInput {
+        buttons: device.state.digital_state.clone(),
+        axes: device.state.analog_state.clone(),
+        hats: device.state.hat_state.clone(),
     }
-    arr
 }
 
-fn to_analog_state_array(state: &Vec<f32>) -> [f32; MAX_ANALOG] {
-    let mut arr = [0.0; MAX_ANALOG];
-    for (place, element) in arr.iter_mut().zip(state.iter()) {
-        *place = *element;
+/// Controllers of the IOKit HID manager
+pub struct HidBackend {
+    controllers: Vec<Controller>,
+    /// Devices claimed by another backend
+    skipped: Vec<Weak<RefCell<hid::Device>>>,
+    next_key: usize,
+    hid: hid::HID,
+}
+
+/// Run the raw HID state of a device through its mapping
+fn apply_mapping(
+    mapping: &Mapping,
+    matches: &mut AxisMatches,
+    device_state: &hid::DeviceState,
+    state: &mut ControllerState,
+) {
+    for (index, pressed) in device_state.digital_state.iter().enumerate() {
+        mapping.set_button(state, index, *pressed);
+    }
+    for (index, value) in device_state.analog_state.iter().enumerate() {
+        mapping.set_axis(state, matches, index, *value);
+    }
+    for (index, bits) in device_state.hat_state.iter().enumerate() {
+        mapping.set_hat(state, index, *bits);
     }
-    arr
 }
 
-impl ControllerContext {
-    pub fn new() -> Option<Self> {
-        let mut info = Vec::new();
-        let mut state = Vec::new();
-        for _ in 0..MAX_DEVICES {
-            info.push(ControllerInfo::new());
-            state.push(ControllerState::new());
-        }
+impl HidBackend {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            controllers: vec![],
+            skipped: vec![],
+            next_key: 0,
+            hid: hid::HID::new()?,
+        })
+    }
 
-        let hid = hid::HID::new();
-
-        match hid {
-            Ok(hid) => Some({
-                let mappings = crate::mapping::read_mappings_file(crate::mapping::Platform::Mac);
-                let mut context = Self {
-                    info,
-                    state,
-                    hid,
-                    mappings,
-                };
-                context.scan_controllers();
-                context
-            }),
-            Err(err) => {
-                println!("Error on create HID. reason: {:?}", err);
-                None
-            }
-        }
+    fn controller(&self, device: usize) -> Option<&Controller> {
+        self.controllers
+            .iter()
+            .find(|controller| controller.key == device)
     }
+}
 
-    fn scan_controllers(&mut self) -> usize {
+impl Backend for HidBackend {
+    /// Add the devices HID found since the last scan
+    fn enumerate(
+        &mut self,
+        mappings: &Mappings,
+        claimed: &dyn Fn(u16, u16) -> bool,
+    ) -> Result<Vec<usize>, Error> {
         self.hid.detect_devices();
 
-        let ndev = self.hid.num_devices();
         let state = self.hid.hid_state();
         let devices = state.devices.borrow();
-
-        for (i, dev) in devices.iter().enumerate() {
-            if i >= MAX_DEVICES {
-                break;
+        self.skipped.retain(|dev| dev.strong_count() > 0);
+
+        let mut keys = vec![];
+        for dev in devices.iter() {
+            let known = self
+                .controllers
+                .iter()
+                .map(|controller| &controller.device)
+                .chain(self.skipped.iter())
+                .any(|known| Weak::ptr_eq(known, dev));
+            let d = match dev.upgrade() {
+                Some(d) if !known => d,
+                _ => continue,
+            };
+            let d = d.borrow();
+
+            if claimed(d.vendor_id, d.product_id) {
+                self.skipped.push(dev.clone());
+                report(Diagnostic::DeviceRejected {
+                    path: format!("{:08x}", d.location.unwrap_or(0)),
+                    reason: "driven by another backend",
+                });
+                continue;
             }
 
-            if let Some(d) = dev.upgrade() {
-                use crate::GamepadButton;
-
-                let d = d.borrow();
-
-                self.info[i] = ControllerInfo {
-                    name: d.name.clone(),
-                    analog_count: d.axes.len(),
-                    buttons: vec![
-                        GamepadButton::A,
-                        GamepadButton::B,
-                        GamepadButton::X,
-                        GamepadButton::Y,
-                        GamepadButton::Back,
-                        GamepadButton::Start,
-                        GamepadButton::BumperLeft,
-                        GamepadButton::BumperRight,
-                        GamepadButton::ThumbLeft,
-                        GamepadButton::ThumbRight,
-                    ],
-                };
-                self.state[i].status = ControllerStatus::Connected;
-            } else {
-                self.info[i] = ControllerInfo::new();
-                self.state[i].status = ControllerStatus::Disconnected;
-            }
+            let info = ControllerInfo {
+                name: d.name.clone(),
+                vendor_id: d.vendor_id,
+                product_id: d.product_id,
+                version: d.version,
+                bus: match d.transport.as_deref() {
+                    Some("USB") => BusType::Usb,
+                    Some("Bluetooth") | Some("Bluetooth Low Energy") => BusType::Bluetooth,
+                    Some("Virtual") => BusType::Virtual,
+                    _ => BusType::Other(0),
+                },
+                guid: d.guid,
+                controller_type: ControllerType::from_ids(d.vendor_id, d.product_id),
+                serial: d.serial.clone(),
+                physical_path: d.location.map(|location| format!("{:08x}", location)),
+                ..ControllerInfo::new()
+            };
+            report(Diagnostic::DeviceFound {
+                name: info.name.clone(),
+                guid: info.guid,
+                path: info.physical_path.clone(),
+            });
+            let mut state = ControllerState::new();
+            state.status = ControllerStatus::Connected;
+            let mut controller = Controller {
+                key: self.next_key,
+                device: dev.clone(),
+                info,
+                state,
+                mapping: Mapping::new(d.guid),
+                axis_matches: AxisMatches::default(),
+            };
+            let (mapping, origin) = Controller::find_mapping(mappings, &d);
+            controller.set_mapping(mapping, origin, &d);
+            keys.push(controller.key);
+            self.controllers.push(controller);
+            self.next_key += 1;
         }
-
-        ndev
+        Ok(keys)
     }
 
-    pub fn update(&mut self) {
-        for index in 0..MAX_DEVICES {
-            self.hid.update(index);
+    fn poll(&mut self) {
+        for controller in &mut self.controllers {
+            controller.state.begin_update();
+            let device = match controller.device.upgrade() {
+                Some(device) => device,
+                None => {
+                    controller.state.status = ControllerStatus::Disconnected;
+                    continue;
+                }
+            };
+            self.hid.update(&device);
+            let dev_bor = device.borrow();
+
+            controller.state.sequence = dev_bor.state.sequence;
+            apply_mapping(
+                &controller.mapping,
+                &mut controller.axis_matches,
+                &dev_bor.state,
+                &mut controller.state,
+            );
+        }
+    }
 
-            let state = self.hid.hid_state();
-            let devices = state.devices.borrow();
+    fn close(&mut self, device: usize) {
+        self.controllers
+            .retain(|controller| controller.key != device);
+    }
 
-            if index >= devices.len() || index >= MAX_DEVICES {
-                return;
-            }
+    fn info(&self, device: usize) -> Option<&ControllerInfo> {
+        self.controller(device).map(|controller| &controller.info)
+    }
 
-            let dev = &devices[index];
-
-            if let Some(d) = dev.upgrade() {
-                let dev_bor = d.borrow();
-
-                let mapping = self
-                    .mappings
-                    .get(&dev_bor.guid)
-                    .cloned()
-                    .unwrap_or_else(|| {
-                        println!("No mapping for {}, falling back to default!", &dev_bor.guid);
-                        crate::mapping::Mapping::new(&dev_bor.guid)
-                    });
-
-                self.state[index].sequence = dev_bor.state.sequence;
-                self.state[index].analog_state = to_analog_state_array(&dev_bor.state.analog_state);
-                self.state[index].digital_state_prev = self.state[index].digital_state;
-                self.state[index].digital_state =
-                    to_digital_state_array(&mapping, &dev_bor.state.digital_state);
-            } else {
-                self.state[index].status = ControllerStatus::Disconnected;
-            }
-        }
+    fn state(&self, device: usize) -> Option<&ControllerState> {
+        self.controller(device).map(|controller| &controller.state)
     }
 
-    /// Get current information of Controller
-    pub fn info(&self, index: usize) -> ControllerInfo {
-        if index < MAX_DEVICES {
-            self.info[index].clone()
-        } else {
-            ControllerInfo::new()
-        }
+    fn raw_input(&self, device: usize) -> Option<RawInput> {
+        let device = self.controller(device)?.device.upgrade()?;
+        let device = device.borrow();
+        Some(raw_input(&device))
     }
 
-    /// Get current state of Controller
-    pub fn state(&self, index: usize) -> &ControllerState {
-        if index < MAX_DEVICES {
-            &self.state[index]
-        } else {
-            &DEFAULT_CONTROLLER_STATE
+    fn remap(&mut self, mappings: &Mappings) {
+        for controller in &mut self.controllers {
+            if let Some(device) = controller.device.upgrade() {
+                let device = device.borrow();
+                let (mapping, origin) = Controller::find_mapping(mappings, &device);
+                if mapping != controller.mapping || origin != controller.info.mapping {
+                    controller.set_mapping(mapping, origin, &device);
+                }
+            }
         }
     }
 }
+
+pub(crate) fn backends() -> Result<Vec<Box<dyn Backend>>, Error> {
+    Ok(vec![Box::new(HidBackend::new()?)])
+}
@@ -17,15 +17,12 @@ use std::rc::{Rc, Weak};
 
 use super::io_kit::{self, *};
 
+use crate::{Error, Guid};
+
 fn gamepad_rs_runloop_mode() -> CFString {
     "GamepadRS".into()
 }
 
-#[derive(Debug)]
-pub enum Error {
-    Unknown(String),
-}
-
 type HIDResult<T> = Result<T, Error>;
 
 pub struct HIDStateContext {
@@ -108,6 +105,16 @@ impl HIDElement {
     }
 
     fn query_state(&mut self, device_ref: IOHIDDeviceRef) -> Option<i32> {
+        let value = self.query_value(device_ref)?;
+
+        // record min and max for auto calibration
+        self.min_report = self.min_report.min(value);
+        self.max_report = self.max_report.max(value);
+
+        Some(value)
+    }
+
+    fn query_value(&self, device_ref: IOHIDDeviceRef) -> Option<i32> {
         use std::mem;
 
         if device_ref == ptr::null_mut() || self.ref_elem == ptr::null_mut() {
@@ -119,18 +126,36 @@ impl HIDElement {
             if IOHIDDeviceGetValue(device_ref, self.ref_elem, mem::transmute(&mut value_ref))
                 == kIOReturnSuccess
             {
-                let value = IOHIDValueGetIntegerValue(value_ref) as i32;
-
-                // record min and max for auto calibration
-                self.min_report = self.min_report.min(value);
-                self.max_report = self.max_report.max(value);
-
-                return Some(value);
+                return Some(IOHIDValueGetIntegerValue(value_ref) as i32);
             }
         }
 
         None
     }
+
+    /// Hat position as `mapping::HAT_*` bits
+    fn query_hat(&self, device_ref: IOHIDDeviceRef) -> Option<u8> {
+        use crate::mapping::{HAT_DOWN, HAT_LEFT, HAT_RIGHT, HAT_UP};
+
+        let mut position = self.query_value(device_ref)? - self.min_report;
+        // Convert 4-way hats to 8-way
+        if self.max_report - self.min_report + 1 == 4 {
+            position *= 2;
+        }
+
+        // Out of range values are the centered position
+        Some(match position {
+            0 => HAT_UP,
+            1 => HAT_UP | HAT_RIGHT,
+            2 => HAT_RIGHT,
+            3 => HAT_DOWN | HAT_RIGHT,
+            4 => HAT_DOWN,
+            5 => HAT_DOWN | HAT_LEFT,
+            6 => HAT_LEFT,
+            7 => HAT_UP | HAT_LEFT,
+            _ => 0,
+        })
+    }
 }
 
 struct DeviceContext {
@@ -142,6 +167,7 @@ pub struct DeviceState {
     pub sequence: usize,
     pub digital_state: Vec<bool>,
     pub analog_state: Vec<f32>,
+    pub hat_state: Vec<u8>,
 }
 
 #[derive(Debug)]
@@ -151,7 +177,13 @@ pub struct Device {
     device: IOHIDDeviceRef,
 
     pub name: String,
-    pub guid: String,
+    pub guid: Guid,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub version: u16,
+    pub transport: Option<String>,
+    pub serial: Option<String>,
+    pub location: Option<i32>,
     pub axes: Vec<HIDElement>,
     pub hats: Vec<HIDElement>,
     pub buttons: Vec<HIDElement>,
@@ -191,7 +223,7 @@ unsafe fn get_property_str(dev: IOHIDDeviceRef, s: &'static str) -> Option<Strin
         return None;
     }
 
-    let cf_str = CFString::wrap_under_create_rule(ref_cf as _);
+    let cf_str = CFString::wrap_under_get_rule(ref_cf as _);
     Some(cf_str.to_string())
 }
 
@@ -343,25 +375,24 @@ impl Device {
             let product_id = get_property_i32(dev, kIOHIDProductIDKey()).unwrap_or(0);
             let version = get_property_i32(dev, kIOHIDVersionNumberKey()).unwrap_or(0);
 
-            #[rustfmt::skip]
             let guid = if vendor != 0 && product_id != 0 {
-                format!("03000000{:02x}{:02x}0000{:02x}{:02x}0000{:02x}{:02x}0000",
-                        vendor as u8, (vendor >> 8) as u8,
-                        product_id as u8,  (product_id >> 8) as u8,
-                        version as u8, (version >> 8) as u8)
+                Guid::from_ids(0x03, vendor as u16, product_id as u16, version as u16)
             } else {
-                let name = name.as_bytes();
-                format!("05000000{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}00",
-                        name[0], name[1], name[2], name[3],
-                        name[4], name[5], name[6], name[7],
-                        name[8], name[9], name[10])
-            };
+                Guid::from_name(0x05, name.as_bytes())
+            }
+            .with_name_crc(name.as_bytes());
 
             let mut device = Device {
                 usage,
                 page,
                 guid,
                 name,
+                vendor_id: vendor as u16,
+                product_id: product_id as u16,
+                version: version as u16,
+                transport: get_property_str(dev, kIOHIDTransportKey()),
+                serial: get_property_str(dev, kIOHIDSerialNumberKey()),
+                location: get_property_i32(dev, kIOHIDLocationIDKey()),
                 device: dev,
                 hats: Vec::new(),
                 axes: Vec::new(),
@@ -467,22 +498,6 @@ impl HIDState {
 }
 
 impl HID {
-    // Return number of devices
-    pub fn num_devices(&self) -> usize {
-        let mut n = 0;
-
-        let state = self.hid_state();
-        let devices = state.devices.borrow();
-
-        for dev in devices.iter() {
-            if let Some(_) = dev.upgrade() {
-                n += 1;
-            }
-        }
-
-        n
-    }
-
     // Query the new devices is inserted or not
     pub fn detect_devices(&mut self) {
         // Remove all empty weak pointer
@@ -501,19 +516,7 @@ impl HID {
         }
     }
 
-    pub fn update(&mut self, dev_index: usize) {
-        let state = self.hid_state();
-        let devices = state.devices.borrow();
-        if dev_index >= devices.len() {
-            return;
-        }
-
-        let dev = &devices[dev_index].upgrade();
-        if dev.is_none() {
-            return;
-        }
-
-        let dev = dev.as_ref().unwrap();
+    pub fn update(&mut self, dev: &RefCell<Device>) {
         let mut dev_bor = dev.borrow_mut();
         let device_ref = dev_bor.device;
 
@@ -531,6 +534,12 @@ impl HID {
             }
         }
 
+        for hat in dev_bor.hats.iter() {
+            if let Some(state) = hat.query_hat(device_ref) {
+                new_state.hat_state.push(state);
+            }
+        }
+
         dev_bor.state = new_state;
     }
 
@@ -540,7 +549,7 @@ impl HID {
                 IOHIDManagerCreate(cf::base::kCFAllocatorDefault as _, kIOHIDManagerOptionNone);
 
             if kIOReturnSuccess != IOHIDManagerOpen(hidman, kIOHIDOptionsTypeNone) {
-                return Err(Error::Unknown("Fail to open HID Manager".to_owned()));
+                return Err(Error::Platform("failed to open the HID manager".to_owned()));
             }
 
             CFRetain(hidman as _);
@@ -66,6 +66,18 @@ pub fn kIOHIDProductIDKey() -> &'static str {
     "ProductID"
 }
 
+pub fn kIOHIDTransportKey() -> &'static str {
+    "Transport"
+}
+
+pub fn kIOHIDSerialNumberKey() -> &'static str {
+    "SerialNumber"
+}
+
+pub fn kIOHIDLocationIDKey() -> &'static str {
+    "LocationID"
+}
+
 pub fn kIOHIDDeviceUsageKey() -> &'static str {
     "DeviceUsage"
 }
@@ -1,23 +1,26 @@
 use std::mem;
+use std::time::{Duration, Instant};
 
 use winapi::shared::winerror::ERROR_SUCCESS;
 use winapi::um::xinput::{
     self, XINPUT_CAPABILITIES as XCapabilities, XINPUT_FLAG_GAMEPAD, XINPUT_GAMEPAD_A,
-    XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK, XINPUT_GAMEPAD_LEFT_SHOULDER, XINPUT_GAMEPAD_LEFT_THUMB,
-    XINPUT_GAMEPAD_RIGHT_SHOULDER, XINPUT_GAMEPAD_RIGHT_THUMB, XINPUT_GAMEPAD_START,
-    XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y, XINPUT_STATE as XState,
+    XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK, XINPUT_GAMEPAD_DPAD_DOWN, XINPUT_GAMEPAD_DPAD_LEFT,
+    XINPUT_GAMEPAD_DPAD_RIGHT, XINPUT_GAMEPAD_DPAD_UP, XINPUT_GAMEPAD_LEFT_SHOULDER,
+    XINPUT_GAMEPAD_LEFT_THUMB, XINPUT_GAMEPAD_RIGHT_SHOULDER, XINPUT_GAMEPAD_RIGHT_THUMB,
+    XINPUT_GAMEPAD_START, XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y, XINPUT_STATE as XState,
+    XINPUT_VIBRATION,
 };
 
-use super::super::{
-    ControllerInfo, ControllerState, ControllerStatus, DEFAULT_CONTROLLER_STATE, MAX_DIGITAL,
-};
+use super::super::{ControllerInfo, ControllerState, ControllerStatus};
 
-use crate::GamepadButton;
+use crate::diagnostic::report;
+use crate::mapping::{MappingLayer, MappingOrigin, Mappings};
+use crate::{Backend, ControllerType, Diagnostic, Error, GamepadAxis, GamepadButton, Output};
 
 pub struct GamePad {
     info: ControllerInfo,
     state: ControllerState,
-    buttons_map: [u16; MAX_DIGITAL],
+    buttons_map: [u16; GamepadButton::ALL.len()],
     sequence: u32,
 }
 
@@ -37,7 +40,7 @@ impl GamePad {
         name.push_str(" controller");
 
         let mut buttons = vec![];
-        let mut buttons_map = [0; MAX_DIGITAL as usize];
+        let mut buttons_map = [0; GamepadButton::ALL.len()];
         if capabilities.Gamepad.wButtons & XINPUT_GAMEPAD_A != 0 {
             buttons.push(GamepadButton::A);
             buttons_map[GamepadButton::A as usize] = XINPUT_GAMEPAD_A;
@@ -71,6 +74,22 @@ impl GamePad {
             buttons.push(GamepadButton::ThumbRight);
             buttons_map[GamepadButton::ThumbRight as usize] = XINPUT_GAMEPAD_RIGHT_THUMB;
         }
+        if capabilities.Gamepad.wButtons & XINPUT_GAMEPAD_DPAD_UP != 0 {
+            buttons.push(GamepadButton::DpadUp);
+            buttons_map[GamepadButton::DpadUp as usize] = XINPUT_GAMEPAD_DPAD_UP;
+        }
+        if capabilities.Gamepad.wButtons & XINPUT_GAMEPAD_DPAD_DOWN != 0 {
+            buttons.push(GamepadButton::DpadDown);
+            buttons_map[GamepadButton::DpadDown as usize] = XINPUT_GAMEPAD_DPAD_DOWN;
+        }
+        if capabilities.Gamepad.wButtons & XINPUT_GAMEPAD_DPAD_LEFT != 0 {
+            buttons.push(GamepadButton::DpadLeft);
+            buttons_map[GamepadButton::DpadLeft as usize] = XINPUT_GAMEPAD_DPAD_LEFT;
+        }
+        if capabilities.Gamepad.wButtons & XINPUT_GAMEPAD_DPAD_RIGHT != 0 {
+            buttons.push(GamepadButton::DpadRight);
+            buttons_map[GamepadButton::DpadRight as usize] = XINPUT_GAMEPAD_DPAD_RIGHT;
+        }
         if capabilities.Gamepad.wButtons & XINPUT_GAMEPAD_LEFT_SHOULDER != 0 {
             buttons.push(GamepadButton::BumperLeft);
             buttons_map[GamepadButton::BumperLeft as usize] = XINPUT_GAMEPAD_LEFT_SHOULDER;
@@ -82,9 +101,11 @@ impl GamePad {
 
         let mut analog_count = 0;
         if capabilities.Gamepad.bLeftTrigger != 0 {
+            buttons.push(GamepadButton::TriggerLeft);
             analog_count += 1;
         }
         if capabilities.Gamepad.bRightTrigger != 0 {
+            buttons.push(GamepadButton::TriggerRight);
             analog_count += 1;
         }
         if capabilities.Gamepad.sThumbLX != 0 {
@@ -105,6 +126,12 @@ impl GamePad {
                 name,
                 buttons,
                 analog_count,
+                // XInput does not tell anything about the device itself
+                guid: "xinput".parse().unwrap(),
+                // What SDL reports for XInput devices too
+                controller_type: ControllerType::Xbox360,
+                mapping: MappingOrigin::new(MappingLayer::Native),
+                ..ControllerInfo::new()
             },
             state: ControllerState {
                 status: ControllerStatus::Connected,
@@ -116,7 +143,7 @@ impl GamePad {
     }
 
     pub fn update(&mut self, state: &XState) {
-        self.state.digital_state_prev = self.state.digital_state;
+        self.state.begin_update();
 
         if state.dwPacketNumber == self.sequence {
             // no change in state
@@ -125,75 +152,162 @@ impl GamePad {
 
         self.sequence = state.dwPacketNumber;
         for button in &self.info.buttons {
-            self.state.digital_state[*button as usize] =
-                state.Gamepad.wButtons & self.buttons_map[*button as usize] != 0;
-        }
-        self.state.analog_state[0] =
-            (state.Gamepad.sThumbLX as i32 + 32768) as f32 / 65535.0 * 2.0 - 1.0;
-        self.state.analog_state[1] =
-            (state.Gamepad.sThumbLY as i32 + 32768) as f32 / 65535.0 * 2.0 - 1.0;
-        self.state.analog_state[2] =
-            (state.Gamepad.sThumbRX as i32 + 32768) as f32 / 65535.0 * 2.0 - 1.0;
-        self.state.analog_state[3] =
-            (state.Gamepad.sThumbRY as i32 + 32768) as f32 / 65535.0 * 2.0 - 1.0;
+            // The digital triggers come from the trigger axes
+            let mask = self.buttons_map[*button as usize];
+            if mask != 0 {
+                self.state
+                    .set_button(*button, state.Gamepad.wButtons & mask != 0);
+            }
+        }
+        // XInput has Y up, SDL and the other backends have it down
+        let pad = &state.Gamepad;
+        self.state
+            .set_axis(GamepadAxis::LeftX, stick_value(pad.sThumbLX));
+        self.state
+            .set_axis(GamepadAxis::LeftY, -stick_value(pad.sThumbLY));
+        self.state
+            .set_axis(GamepadAxis::RightX, stick_value(pad.sThumbRX));
+        self.state
+            .set_axis(GamepadAxis::RightY, -stick_value(pad.sThumbRY));
+        self.state
+            .set_axis(GamepadAxis::LeftTrigger, pad.bLeftTrigger as f32 / 255.0);
+        self.state
+            .set_axis(GamepadAxis::RightTrigger, pad.bRightTrigger as f32 / 255.0);
     }
 }
-pub struct ControllerContext {
+
+/// -1.0..1.0 from the raw stick value
+fn stick_value(value: i16) -> f32 {
+    (value as i32 + 32768) as f32 / 65535.0 * 2.0 - 1.0
+}
+
+/// How often the free user indices are probed, like the Linux backends scan
+/// for devices. `XInputGetCapabilities` can block for milliseconds on an
+/// index without a controller.
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The four XInput user indices
+pub struct XInputBackend {
+    /// Controller of each user index, which is its key
     gamepads: [Option<GamePad>; 4],
+    last_scan: Option<Instant>,
 }
 
-impl ControllerContext {
-    pub fn new() -> Option<Self> {
+impl XInputBackend {
+    pub fn new() -> XInputBackend {
         unsafe { xinput::XInputEnable(1) };
 
-        Some(Self {
+        XInputBackend {
             gamepads: [None, None, None, None],
-        })
+            last_scan: None,
+        }
+    }
+}
+
+impl Default for XInputBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for XInputBackend {
+    /// XInput controllers all share one fixed layout, `mappings` are not
+    /// used, and XInput does not tell the ids of the devices
+    fn enumerate(
+        &mut self,
+        _mappings: &Mappings,
+        _claimed: &dyn Fn(u16, u16) -> bool,
+    ) -> Result<Vec<usize>, Error> {
+        if self
+            .last_scan
+            .is_some_and(|last_scan| last_scan.elapsed() < SCAN_INTERVAL)
+        {
+            return Ok(vec![]);
+        }
+        self.last_scan = Some(Instant::now());
+
+        let mut keys = vec![];
+        for (index, slot) in self.gamepads.iter_mut().enumerate() {
+            if slot.is_some() {
+                continue;
+            }
+
+            let mut capabilities = unsafe { mem::zeroed::<XCapabilities>() };
+            if unsafe {
+                xinput::XInputGetCapabilities(index as u32, XINPUT_FLAG_GAMEPAD, &mut capabilities)
+            } == ERROR_SUCCESS
+            {
+                let mut gamepad = GamePad::new(&capabilities);
+                // The user index is the closest XInput has to a port, for
+                // giving a controller plugged back in its id again
+                gamepad.info.physical_path = Some(format!("XInput {}", index));
+                report(Diagnostic::DeviceFound {
+                    name: gamepad.info.name.clone(),
+                    guid: gamepad.info.guid,
+                    path: gamepad.info.physical_path.clone(),
+                });
+                report(Diagnostic::mapping(&gamepad.info));
+                *slot = Some(gamepad);
+                keys.push(index);
+            }
+        }
+        Ok(keys)
     }
 
-    pub fn update(&mut self) {
-        for index in 0..4 {
+    fn poll(&mut self) {
+        for (index, slot) in self.gamepads.iter_mut().enumerate() {
+            let gamepad = match slot {
+                Some(gamepad) => gamepad,
+                None => continue,
+            };
+
             let mut state = unsafe { mem::zeroed::<XState>() };
-            let val = unsafe { xinput::XInputGetState(index as u32, &mut state) };
-
-            if val == ERROR_SUCCESS {
-                if self.gamepads[index].is_none() {
-                    let mut capabilities = unsafe { mem::zeroed::<XCapabilities>() };
-                    if unsafe {
-                        xinput::XInputGetCapabilities(
-                            index as u32,
-                            XINPUT_FLAG_GAMEPAD,
-                            &mut capabilities,
-                        )
-                    } == ERROR_SUCCESS
-                    {
-                        let gamepad = GamePad::new(&capabilities);
-                        self.gamepads[index] = Some(gamepad);
-                    }
-                }
-
-                if let Some(ref mut gamepad) = &mut self.gamepads[index] {
-                    gamepad.update(&state);
-                }
+            if unsafe { xinput::XInputGetState(index as u32, &mut state) } == ERROR_SUCCESS {
+                gamepad.update(&state);
             } else {
-                self.gamepads[index] = None;
+                gamepad.state.begin_update();
+                gamepad.state.status = ControllerStatus::Disconnected;
             }
         }
     }
 
-    pub fn info(&self, index: usize) -> ControllerInfo {
-        if let Some(Some(gamepad)) = self.gamepads.get(index) {
-            gamepad.info.clone()
-        } else {
-            ControllerInfo::new()
+    fn close(&mut self, device: usize) {
+        if let Some(slot) = self.gamepads.get_mut(device) {
+            *slot = None;
         }
     }
 
-    pub fn state(&self, index: usize) -> &ControllerState {
-        if let Some(Some(gamepad)) = self.gamepads.get(index) {
-            &gamepad.state
-        } else {
-            &DEFAULT_CONTROLLER_STATE
+    fn info(&self, device: usize) -> Option<&ControllerInfo> {
+        self.gamepads
+            .get(device)?
+            .as_ref()
+            .map(|gamepad| &gamepad.info)
+    }
+
+    fn state(&self, device: usize) -> Option<&ControllerState> {
+        self.gamepads
+            .get(device)?
+            .as_ref()
+            .map(|gamepad| &gamepad.state)
+    }
+
+    fn output(&mut self, device: usize, output: Output) -> bool {
+        if !matches!(self.gamepads.get(device), Some(Some(_))) {
+            return false;
+        }
+
+        match output {
+            Output::Rumble { low, high } => {
+                let mut vibration = XINPUT_VIBRATION {
+                    wLeftMotorSpeed: (low.clamp(0.0, 1.0) * 65535.0) as u16,
+                    wRightMotorSpeed: (high.clamp(0.0, 1.0) * 65535.0) as u16,
+                };
+                unsafe { xinput::XInputSetState(device as u32, &mut vibration) == ERROR_SUCCESS }
+            }
         }
     }
 }
+
+pub(crate) fn backends() -> Result<Vec<Box<dyn Backend>>, Error> {
+    Ok(vec![Box::new(XInputBackend::new())])
+}
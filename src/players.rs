@@ -0,0 +1,299 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{ControllerInfo, Guid};
+
+/// Handle of a controller. It stays the same while the controller is
+/// connected, and the controller gets it back when it is plugged in again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ControllerId(u64);
+
+impl fmt::Display for ControllerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How long the player slot of a disconnected controller is kept for it
+const RESERVATION: Duration = Duration::from_secs(30);
+
+/// A controller seen since the context was created
+struct Known {
+    id: ControllerId,
+    serial: Option<String>,
+    physical_path: Option<String>,
+    guid: Guid,
+    /// When the controller was disconnected, `None` while it is connected
+    disconnected: Option<Instant>,
+    /// Player slot, kept after a disconnect to give it back on reconnect
+    player: usize,
+}
+
+impl Known {
+    /// Whether the controller is gone and has slot `player` kept for it
+    fn reserves(&self, player: usize, time: Instant) -> bool {
+        self.player == player
+            && self
+                .disconnected
+                .is_some_and(|disconnected| time.duration_since(disconnected) < RESERVATION)
+    }
+
+    /// Another controller with a serial number of its own
+    fn other_serial(&self, info: &ControllerInfo) -> bool {
+        matches!((&self.serial, &info.serial), (Some(a), Some(b)) if a != b)
+    }
+}
+
+/// Ids and player slots of the controllers of every backend
+pub(crate) struct Players {
+    known: Vec<Known>,
+    /// Controller of each player slot
+    slots: Vec<Option<ControllerId>>,
+}
+
+impl Players {
+    pub fn new() -> Players {
+        Players {
+            known: vec![],
+            slots: vec![],
+        }
+    }
+
+    /// Id for a controller that just connected. A controller that was
+    /// connected before, with the same serial number, or else the same
+    /// physical path and GUID, or else the same GUID as the only
+    /// disconnected controller with it, takes back its id and its player slot
+    /// when the slot is still free. Another controller of a model of which
+    /// several are gone gets an id of its own.
+    pub fn connect(&mut self, info: &ControllerInfo, time: Instant) -> ControllerId {
+        let index = match self.reconnecting(info) {
+            Some(index) => index,
+            None => {
+                self.known.push(Known {
+                    id: ControllerId(self.known.len() as u64),
+                    serial: None,
+                    physical_path: None,
+                    guid: info.guid,
+                    disconnected: Some(time),
+                    player: usize::MAX,
+                });
+                self.known.len() - 1
+            }
+        };
+
+        let player = match self.slots.get(self.known[index].player) {
+            Some(None) => self.known[index].player,
+            _ => self.free_slot(time),
+        };
+        if player == self.slots.len() {
+            self.slots.push(None);
+        }
+        self.slots[player] = Some(self.known[index].id);
+
+        let known = &mut self.known[index];
+        // Plugged back in another port, or paired again
+        known.serial = info.serial.clone();
+        known.physical_path = info.physical_path.clone();
+        known.disconnected = None;
+        known.player = player;
+        known.id
+    }
+
+    fn reconnecting(&self, info: &ControllerInfo) -> Option<usize> {
+        let disconnected = || {
+            self.known
+                .iter()
+                .enumerate()
+                .filter(|(_, known)| known.disconnected.is_some())
+        };
+
+        disconnected()
+            .find(|(_, known)| known.serial.is_some() && known.serial == info.serial)
+            .or_else(|| {
+                disconnected().find(|(_, known)| {
+                    !known.other_serial(info)
+                        && known.physical_path.is_some()
+                        && known.physical_path == info.physical_path
+                        && known.guid == info.guid
+                })
+            })
+            .or_else(|| {
+                // Paired again or plugged in elsewhere, when no other
+                // controller of the model could be coming back
+                let mut same_model = disconnected().filter(|(_, known)| known.guid == info.guid);
+                match (same_model.next(), same_model.next()) {
+                    (Some(found), None) if !found.1.other_serial(info) => Some(found),
+                    _ => None,
+                }
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// The first slot that no one has and that is not kept for a controller
+    /// that disconnected less than `RESERVATION` ago
+    fn free_slot(&self, time: Instant) -> usize {
+        (0..self.slots.len())
+            .find(|player| {
+                self.slots[*player].is_none()
+                    && !self.known.iter().any(|known| known.reserves(*player, time))
+            })
+            .unwrap_or(self.slots.len())
+    }
+
+    /// Free the player slot of `id`, the controller gets it back if it
+    /// reconnects before someone else takes it. It is kept for it for
+    /// `RESERVATION`.
+    pub fn disconnect(&mut self, id: ControllerId, time: Instant) {
+        if let Some(known) = self.known.iter_mut().find(|known| known.id == id) {
+            known.disconnected = Some(time);
+            self.slots[known.player] = None;
+        }
+    }
+
+    /// The connected controllers, by player slot
+    pub fn ids(&self) -> Vec<ControllerId> {
+        self.slots.iter().flatten().copied().collect()
+    }
+
+    pub fn id(&self, player: usize) -> Option<ControllerId> {
+        self.slots.get(player).copied().flatten()
+    }
+
+    pub fn player(&self, id: ControllerId) -> Option<usize> {
+        self.known
+            .iter()
+            .find(|known| known.id == id && known.disconnected.is_none())
+            .map(|known| known.player)
+    }
+
+    /// Move `id` to slot `player`, the controller that had it gets the old
+    /// slot of `id`. False when `id` is not connected.
+    pub fn set_player(&mut self, id: ControllerId, player: usize) -> bool {
+        let old = match self.player(id) {
+            Some(old) => old,
+            None => return false,
+        };
+        if player >= self.slots.len() {
+            self.slots.resize(player + 1, None);
+        }
+
+        let other = self.slots[player];
+        self.slots[old] = other;
+        self.slots[player] = Some(id);
+        for known in &mut self.known {
+            if known.id == id {
+                known.player = player;
+            } else if Some(known.id) == other {
+                known.player = old;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(serial: Option<&str>, physical_path: &str) -> ControllerInfo {
+        ControllerInfo {
+            guid: "030000005e0400008e02000014010000".parse().unwrap(),
+            serial: serial.map(str::to_owned),
+            physical_path: Some(physical_path.to_owned()),
+            ..ControllerInfo::new()
+        }
+    }
+
+    #[test]
+    fn reconnect_by_serial() {
+        let mut players = Players::new();
+        let time = Instant::now();
+        let first = players.connect(&pad(Some("aa"), "usb-1"), time);
+        let second = players.connect(&pad(Some("bb"), "usb-2"), time);
+        assert_eq!(players.ids(), [first, second]);
+
+        // Back in another port
+        players.disconnect(first, time);
+        assert_eq!(players.id(0), None);
+        let third = players.connect(&pad(Some("cc"), "usb-1"), time);
+        assert_eq!(players.player(third), Some(2));
+        assert_eq!(players.connect(&pad(Some("aa"), "usb-3"), time), first);
+        assert_eq!(players.player(first), Some(0));
+
+        assert!(players.set_player(third, 0));
+        assert_eq!(players.ids(), [third, second, first]);
+    }
+
+    #[test]
+    fn reconnect_by_port() {
+        let mut players = Players::new();
+        let time = Instant::now();
+        let first = players.connect(&pad(None, "usb-1"), time);
+        let second = players.connect(&pad(None, "usb-2"), time);
+        players.disconnect(first, time);
+        players.disconnect(second, time);
+        assert_eq!(players.connect(&pad(None, "usb-2"), time), second);
+        assert_eq!(players.player(second), Some(1));
+        assert_eq!(players.connect(&pad(None, "usb-1"), time), first);
+        assert_eq!(players.player(first), Some(0));
+    }
+
+    #[test]
+    fn reconnect_by_guid() {
+        let mut players = Players::new();
+        let time = Instant::now();
+        let first = players.connect(&pad(None, "bluetooth-1"), time);
+        players.disconnect(first, time);
+        assert_eq!(players.connect(&pad(None, "bluetooth-2"), time), first);
+        assert_eq!(players.player(first), Some(0));
+
+        // Not when it has another serial number
+        let second = players.connect(&pad(Some("aa"), "bluetooth-3"), time);
+        players.disconnect(second, time);
+        let other = players.connect(&pad(Some("bb"), "bluetooth-4"), time);
+        assert_ne!(other, second);
+    }
+
+    #[test]
+    fn same_model_in_another_port() {
+        let mut players = Players::new();
+        let time = Instant::now();
+        let first = players.connect(&pad(None, "usb-1"), time);
+        let second = players.connect(&pad(None, "usb-2"), time);
+        players.disconnect(first, time);
+        players.disconnect(second, time);
+
+        // Either could be back
+        let other = players.connect(&pad(None, "usb-3"), time);
+        assert_ne!(other, first);
+        assert_ne!(other, second);
+        // The slots of the others stay free for them
+        assert_eq!(players.player(other), Some(2));
+        assert_eq!(players.connect(&pad(None, "usb-1"), time), first);
+        assert_eq!(players.ids(), [first, other]);
+    }
+
+    #[test]
+    fn reservation_runs_out() {
+        let mut players = Players::new();
+        let time = Instant::now();
+        let first = players.connect(&pad(Some("aa"), "usb-1"), time);
+        let second = players.connect(&pad(Some("bb"), "usb-2"), time);
+        players.disconnect(first, time);
+
+        let third = players.connect(&pad(Some("cc"), "usb-3"), time + RESERVATION / 2);
+        assert_eq!(players.player(third), Some(2));
+
+        // Player 1 goes to the next controller once it has waited long enough
+        let fourth = players.connect(&pad(Some("dd"), "usb-4"), time + RESERVATION);
+        assert_eq!(players.player(fourth), Some(0));
+        assert_eq!(players.ids(), [fourth, second, third]);
+
+        // The first one gets the next free slot when it is back
+        assert_eq!(
+            players.connect(&pad(Some("aa"), "usb-1"), time + RESERVATION),
+            first
+        );
+        assert_eq!(players.player(first), Some(3));
+    }
+}